@@ -0,0 +1,56 @@
+extern crate bitstamp;
+
+use std::time::Instant;
+
+use bitstamp::book::{DiffDecoder, LiveOrderBook};
+use bitstamp::types::{CurrencyPairs, Event, EventChannel, OrderBook};
+
+const DIFFS: u64 = 100_000;
+const LEVELS: u64 = 20;
+
+/// A busy diff stream: every message moves `LEVELS` levels on each side
+fn messages() -> Vec<String> {
+    (0..DIFFS)
+        .map(|i| {
+            let side = |base: u64| {
+                (0..LEVELS)
+                    .map(|l| format!(r#"["{}.{:02}", "{}.{:08}"]"#, base + l, i % 100, (i + l) % 3, i % 100_000_000))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!(
+                r#"{{"data": {{"timestamp": "1633024800", "microtimestamp": "{}", "bids": [{}], "asks": [{}]}}, "channel": "diff_order_book_btcusd", "event": "data"}}"#,
+                1633024800000000 + i + 1,
+                side(43000),
+                side(43100),
+            )
+        })
+        .collect()
+}
+
+fn main() {
+    let messages = messages();
+    let snapshot = OrderBook { timestamp: "1633024800".to_string(), microtimestamp: "1633024800000000".to_string(), bids: vec![], asks: vec![] };
+
+    let mut book = LiveOrderBook::from_snapshot(&snapshot).unwrap();
+    let start = Instant::now();
+    for message in messages.iter() {
+        let event: Event = serde_json::from_str(message).unwrap();
+        book.apply(&event.data).unwrap();
+    }
+    let parsed = start.elapsed();
+
+    let channel = EventChannel::DiffOrderBook(CurrencyPairs::Btcusd);
+    let mut decoder = DiffDecoder::new();
+    let mut decoded_book = LiveOrderBook::from_snapshot(&snapshot).unwrap();
+    let start = Instant::now();
+    for message in messages.iter() {
+        decoded_book.apply_diff(decoder.decode(message, &channel).unwrap()).unwrap();
+    }
+    let decoded = start.elapsed();
+
+    assert_eq!(book, decoded_book);
+    println!("{} diffs of {} levels a side", DIFFS, LEVELS);
+    println!("Event + apply:            {:?} ({:?} per diff)", parsed, parsed / DIFFS as u32);
+    println!("DiffDecoder + apply_diff: {:?} ({:?} per diff)", decoded, decoded / DIFFS as u32);
+}
@@ -3,16 +3,16 @@ use std::env;
 extern crate env_logger;
 extern crate bitstamp;
 
-const BITSTAMP_ACCESS_SECRET: &'static str = "BITSTAMP_ACCESS_SECRET";
-const BITSTAMP_ACCESS_KEY: &'static str = "BITSTAMP_ACCESS_KEY";
+const BITSTAMP_ACCESS_SECRET: &str = "BITSTAMP_ACCESS_SECRET";
+const BITSTAMP_ACCESS_KEY: &str = "BITSTAMP_ACCESS_KEY";
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
     let secret = env::var(BITSTAMP_ACCESS_SECRET)
-        .expect(format!("{} not specified in environment", BITSTAMP_ACCESS_SECRET).as_str());
+        .unwrap_or_else(|_| panic!("{} not specified in environment", BITSTAMP_ACCESS_SECRET));
     let key = env::var(BITSTAMP_ACCESS_KEY)
-        .expect(format!("{} not specified in environment", BITSTAMP_ACCESS_KEY).as_str());
+        .unwrap_or_else(|_| panic!("{} not specified in environment", BITSTAMP_ACCESS_KEY));
 
 
     let bts = bitstamp::Bitstamp::new(secret, key);
@@ -54,11 +54,11 @@ async fn main() {
 
     match bts.event_stream().await {
         Ok(mut ws) => {
-            ws.subscribe(bitstamp::types::EventChannel::LiveTrades(bitstamp::types::CurrencyPairs::Btcusd)).await;
-            ws.subscribe(bitstamp::types::EventChannel::LiveOrders(bitstamp::types::CurrencyPairs::Btcusd)).await;
-            ws.subscribe(bitstamp::types::EventChannel::OrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await;
-            ws.subscribe(bitstamp::types::EventChannel::DetailOrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await;
-            ws.subscribe(bitstamp::types::EventChannel::DiffOrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await;
+            if let Err(e) = ws.subscribe(bitstamp::types::EventChannel::LiveTrades(bitstamp::types::CurrencyPairs::Btcusd)).await { println!("{}", e); }
+            if let Err(e) = ws.subscribe(bitstamp::types::EventChannel::LiveOrders(bitstamp::types::CurrencyPairs::Btcusd)).await { println!("{}", e); }
+            if let Err(e) = ws.subscribe(bitstamp::types::EventChannel::OrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await { println!("{}", e); }
+            if let Err(e) = ws.subscribe(bitstamp::types::EventChannel::DetailOrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await { println!("{}", e); }
+            if let Err(e) = ws.subscribe(bitstamp::types::EventChannel::DiffOrderBook(bitstamp::types::CurrencyPairs::Btcusd)).await { println!("{}", e); }
             loop {
                 match ws.next().await {
                     Ok(event) => { println!("Got event: {:?} at {:?}", event.event, event.data); }
@@ -0,0 +1,28 @@
+extern crate env_logger;
+extern crate bitstamp;
+
+use bitstamp::types::CurrencyPairs;
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    // Public data only, no credentials needed
+    let bts = bitstamp::Bitstamp::builder("".to_string(), "".to_string())
+        .ws_auto_reconnect(true)
+        .build();
+
+    let mut book = match bts.maintained_order_book(CurrencyPairs::Btcusd).await {
+        Ok(book) => book,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    loop {
+        match book.next().await {
+            Ok(live) => { println!("bid {:?} ask {:?}", live.best_bid(), live.best_ask()); }
+            Err(e) => { println!("{}", e); }
+        }
+    }
+}
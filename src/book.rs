@@ -0,0 +1,675 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::error::{text_error, text_error_with_inner, Error};
+use crate::json;
+use crate::stream::Frame;
+use crate::types::{CurrencyPairs, Decimal, Event, EventChannel, EventData, EventEvent, OrderBook, OrderBookTyped, PriceLevel, TradeSide};
+use crate::{Bitstamp, BitstampEventStream};
+
+/// Locally maintained order book: a REST snapshot kept current by applying
+/// `DiffOrderBook` events.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveOrderBook {
+    timestamp: String,
+    microtimestamp: u64,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    max_gap_micros: Option<u64>,
+    sequence_check: SequenceCheck,
+    /// Newest diff seen since the snapshot, applied or not
+    last_diff_micros: Option<u64>,
+}
+
+/// What to do about a diff that isn't newer than the previous diff, see
+/// `LiveOrderBook::with_sequence_check`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SequenceCheck {
+    /// Skip it quietly, like any diff that isn't newer than the book
+    #[default]
+    Off,
+    /// Skip it and log a warning
+    Warn,
+    /// Fail `apply`/`apply_diff` with an error
+    Strict,
+}
+
+/// What `LiveOrderBook::apply` did with a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApplyResult {
+    /// False when the diff was not newer than the book and got skipped
+    pub applied: bool,
+    /// True when the diff came more than `max_gap` after the previous one, so updates were
+    /// probably missed and the book should be resynced from a REST snapshot. The diff is
+    /// still applied.
+    pub sequence_gap_detected: bool,
+    /// True when the diff wasn't newer than the previous diff. Such a diff is never applied.
+    pub out_of_order: bool,
+}
+
+impl LiveOrderBook {
+    pub fn from_snapshot(snapshot: &OrderBook) -> Result<LiveOrderBook, Error> {
+        let mut book = LiveOrderBook {
+            timestamp: snapshot.timestamp.clone(),
+            microtimestamp: parse_microtimestamp(&snapshot.microtimestamp)?,
+            ..Default::default()
+        };
+        update_levels(&mut book.bids, &snapshot.bids)?;
+        update_levels(&mut book.asks, &snapshot.asks)?;
+        Ok(book)
+    }
+
+    /// Flag diffs arriving more than `max_gap` after the previous one (or the snapshot) in
+    /// `ApplyResult::sequence_gap_detected`. Bitstamp diffs carry no sequence number, so the
+    /// gap is judged by `microtimestamp` only. Pick a value above the quietest period you expect
+    /// for the pair. Off by default.
+    pub fn with_max_gap(mut self, max_gap: Duration) -> LiveOrderBook {
+        self.max_gap_micros = Some(max_gap.as_micros() as u64);
+        self
+    }
+
+    /// How to treat diffs whose `microtimestamp` isn't strictly above the previous diff's,
+    /// which points at a reconnect gap or a server problem rather than a diff that's merely
+    /// older than the snapshot. They are flagged in `ApplyResult::out_of_order` whatever the
+    /// setting. Off by default.
+    pub fn with_sequence_check(mut self, sequence_check: SequenceCheck) -> LiveOrderBook {
+        self.sequence_check = sequence_check;
+        self
+    }
+
+    /// Apply a `DiffOrderBook` event. Levels with a zero amount are removed, diffs that
+    /// aren't newer than the book are skipped.
+    pub fn apply(&mut self, data: &EventData) -> Result<ApplyResult, Error> {
+        match data {
+            EventData::OrderBook { timestamp, microtimestamp, bids, asks } => {
+                // Compare as numbers, the strings aren't guaranteed to have the same length
+                let microtimestamp = parse_microtimestamp(microtimestamp)?;
+                let result = self.check_sequence(microtimestamp)?;
+                if !result.applied {
+                    return Ok(result);
+                }
+                update_levels(&mut self.bids, bids)?;
+                update_levels(&mut self.asks, asks)?;
+                self.timestamp = timestamp.clone();
+                self.microtimestamp = microtimestamp;
+                Ok(result)
+            }
+            other => Err(text_error(format!("not an order book diff: {:?}", other))),
+        }
+    }
+
+    /// Apply a diff decoded by `DiffDecoder`, same as `apply` otherwise
+    pub fn apply_diff(&mut self, diff: &Diff) -> Result<ApplyResult, Error> {
+        let result = self.check_sequence(diff.microtimestamp)?;
+        if !result.applied {
+            return Ok(result);
+        }
+        for (side, levels) in [(&mut self.bids, &diff.bids), (&mut self.asks, &diff.asks)] {
+            for level in levels {
+                if level.amount.is_zero() {
+                    side.remove(&level.price);
+                } else {
+                    side.insert(level.price, level.amount);
+                }
+            }
+        }
+        self.timestamp.clone_from(&diff.timestamp);
+        self.microtimestamp = diff.microtimestamp;
+        Ok(result)
+    }
+
+    /// Whether to apply a diff (only if it's newer than the book) and what's off about it
+    fn check_sequence(&mut self, microtimestamp: u64) -> Result<ApplyResult, Error> {
+        let previous = self.last_diff_micros;
+        self.last_diff_micros = Some(previous.map_or(microtimestamp, |previous| previous.max(microtimestamp)));
+        let out_of_order = match previous {
+            Some(previous) if microtimestamp <= previous => {
+                match self.sequence_check {
+                    SequenceCheck::Off => {}
+                    SequenceCheck::Warn => warn!("Order book diff {} is out of order, previous diff was {}", microtimestamp, previous),
+                    SequenceCheck::Strict => return Err(text_error(format!("order book diff {} is out of order, previous diff was {}", microtimestamp, previous))),
+                }
+                true
+            }
+            _ => false,
+        };
+        if microtimestamp <= self.microtimestamp {
+            debug!("Skipping stale diff {} (book at {})", microtimestamp, self.microtimestamp);
+            return Ok(ApplyResult { applied: false, sequence_gap_detected: false, out_of_order });
+        }
+        let sequence_gap_detected = match self.max_gap_micros {
+            Some(max_gap) => microtimestamp - self.microtimestamp > max_gap,
+            None => false,
+        };
+        if sequence_gap_detected {
+            warn!("Order book diff {} came {}us after {}, updates may be missing", microtimestamp, microtimestamp - self.microtimestamp, self.microtimestamp);
+        }
+        Ok(ApplyResult { applied: true, sequence_gap_detected, out_of_order })
+    }
+
+    /// Check that the book is one the exchange could have sent: positive prices and amounts,
+    /// and the best bid below the best ask. Bitstamp publishes no checksum for its books, so
+    /// this is the only way to notice a book that drifted after many diffs.
+    pub fn check_consistency(&self) -> Result<(), String> {
+        for (side, levels) in [("bid", &self.bids), ("ask", &self.asks)] {
+            if let Some((price, amount)) = levels.iter().find(|(price, amount)| !price.is_sign_positive() || !amount.is_sign_positive()) {
+                return Err(format!("{} level {} has amount {}", side, price, amount));
+            }
+        }
+        self.check_crossed()
+    }
+
+    /// The part of `check_consistency` that doesn't look at every level: the best bid has to be
+    /// below the best ask
+    pub fn check_crossed(&self) -> Result<(), String> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) if bid.price >= ask.price => Err(format!("crossed book, best bid {} >= best ask {}", bid.price, ask.price)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn microtimestamp(&self) -> u64 {
+        self.microtimestamp
+    }
+
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.bids.iter().next_back().map(|(price, amount)| PriceLevel { price: *price, amount: *amount })
+    }
+
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.asks.iter().next().map(|(price, amount)| PriceLevel { price: *price, amount: *amount })
+    }
+
+    /// Current state, bids and asks sorted best first
+    pub fn snapshot(&self) -> OrderBookTyped {
+        OrderBookTyped {
+            timestamp: self.timestamp.clone(),
+            microtimestamp: self.microtimestamp.to_string(),
+            bids: self.bids.iter().rev().map(|(price, amount)| PriceLevel { price: *price, amount: *amount }).collect(),
+            asks: self.asks.iter().map(|(price, amount)| PriceLevel { price: *price, amount: *amount }).collect(),
+        }
+    }
+}
+
+/// An order book diff as decoded by `DiffDecoder`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub timestamp: String,
+    pub microtimestamp: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Decodes `DiffOrderBook` messages straight into decimals, reusing its buffers.
+///
+/// Going through `Event` allocates a string for every price and amount of every level, on
+/// the busiest channel there is. Once its vectors have grown to the size of a typical diff
+/// this allocates nothing per update. `MaintainedBook` decodes its diffs with one.
+#[derive(Debug, Default)]
+pub struct DiffDecoder {
+    diff: Diff,
+}
+
+impl DiffDecoder {
+    pub fn new() -> DiffDecoder {
+        DiffDecoder::default()
+    }
+
+    /// Decode the message text of a `data` event on `channel`. Gives `None` for any other
+    /// message and for a diff this can't decode, parse those as an `Event` instead.
+    pub fn decode(&mut self, json: &str, channel: &EventChannel) -> Option<&Diff> {
+        let (event, name) = json::from_str_seed(json, MessageSeed(&mut self.diff)).ok()?;
+        let matches = event == "data" && EventChannel::try_from(name).is_ok_and(|c| c == *channel);
+        matches.then_some(&self.diff)
+    }
+}
+
+/// Reads an event message, decoding `data` into the diff and returning `event` and `channel`
+struct MessageSeed<'a>(&'a mut Diff);
+
+impl<'de, 'a> DeserializeSeed<'de> for MessageSeed<'a> {
+    type Value = (&'de str, &'de str);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for MessageSeed<'a> {
+    type Value = (&'de str, &'de str);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an event message")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (mut event, mut channel, mut data) = (None, None, false);
+        while let Some(key) = map.next_key::<&str>()? {
+            match key {
+                "event" => event = Some(map.next_value()?),
+                "channel" => channel = Some(map.next_value()?),
+                "data" => {
+                    map.next_value_seed(DataSeed(&mut *self.0))?;
+                    data = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        match (event, channel, data) {
+            (Some(event), Some(channel), true) => Ok((event, channel)),
+            _ => Err(de::Error::custom("incomplete event message")),
+        }
+    }
+}
+
+struct DataSeed<'a>(&'a mut Diff);
+
+impl<'de, 'a> DeserializeSeed<'de> for DataSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for DataSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an order book diff")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let diff = self.0;
+        // The fields every diff has to have, a missing one fails like it would for `Event`
+        let (mut timestamp, mut microtimestamp, mut bids, mut asks) = (false, false, false, false);
+        while let Some(key) = map.next_key::<&str>()? {
+            match key {
+                "timestamp" => {
+                    diff.timestamp.clear();
+                    diff.timestamp.push_str(map.next_value()?);
+                    timestamp = true;
+                }
+                "microtimestamp" => {
+                    let value: &str = map.next_value()?;
+                    diff.microtimestamp = value.parse().map_err(|_| de::Error::custom(format!("invalid microtimestamp {:?}", value)))?;
+                    microtimestamp = true;
+                }
+                "bids" => {
+                    map.next_value_seed(LevelsSeed(&mut diff.bids))?;
+                    bids = true;
+                }
+                "asks" => {
+                    map.next_value_seed(LevelsSeed(&mut diff.asks))?;
+                    asks = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if timestamp && microtimestamp && bids && asks {
+            Ok(())
+        } else {
+            Err(de::Error::custom("incomplete order book diff"))
+        }
+    }
+}
+
+/// Decodes `[["price", "amount"], ...]` over the previous levels
+struct LevelsSeed<'a>(&'a mut Vec<PriceLevel>);
+
+impl<'de, 'a> DeserializeSeed<'de> for LevelsSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for LevelsSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of order book levels")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        self.0.clear();
+        while let Some(level) = seq.next_element_seed(LevelSeed)? {
+            self.0.push(level);
+        }
+        Ok(())
+    }
+}
+
+struct LevelSeed;
+
+impl<'de> DeserializeSeed<'de> for LevelSeed {
+    type Value = PriceLevel;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<PriceLevel, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for LevelSeed {
+    type Value = PriceLevel;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an order book level")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PriceLevel, A::Error> {
+        let mut field = || -> Result<Decimal, A::Error> {
+            let value: &str = seq.next_element()?.ok_or_else(|| de::Error::custom("order book level too short"))?;
+            Decimal::from_str(value).map_err(de::Error::custom)
+        };
+        let level = PriceLevel { price: field()?, amount: field()? };
+        // Detail books add an order id
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(level)
+    }
+}
+
+/// A `LiveOrderBook` kept in sync by its own event stream, see `Bitstamp::maintained_order_book`
+pub struct MaintainedBook {
+    client: Bitstamp,
+    pair: CurrencyPairs,
+    stream: BitstampEventStream,
+    book: LiveOrderBook,
+    decoder: DiffDecoder,
+    max_gap: Option<Duration>,
+    sequence_check: SequenceCheck,
+    full_check: bool,
+    resyncs: u64,
+}
+
+/// What `MaintainedBook::next` does after a message
+enum Step {
+    Applied,
+    Skipped,
+    Resync,
+}
+
+impl MaintainedBook {
+    pub(crate) async fn open(client: Bitstamp, pair: CurrencyPairs) -> Result<MaintainedBook, Error> {
+        let mut stream = client.event_stream().await?;
+        stream.subscribe(EventChannel::DiffOrderBook(pair)).await?;
+        let mut book = MaintainedBook {
+            client,
+            pair,
+            stream,
+            book: LiveOrderBook::default(),
+            decoder: DiffDecoder::new(),
+            max_gap: None,
+            sequence_check: SequenceCheck::Off,
+            full_check: false,
+            resyncs: 0,
+        };
+        book.book = book.fetch_snapshot().await?;
+        Ok(book)
+    }
+
+    /// Also resync when diffs arrive more than `max_gap` apart, see `LiveOrderBook::with_max_gap`
+    pub fn set_max_gap(&mut self, max_gap: Duration) {
+        self.max_gap = Some(max_gap);
+        self.book.max_gap_micros = Some(max_gap.as_micros() as u64);
+    }
+
+    /// See `LiveOrderBook::with_sequence_check`, with `Strict` an out of order diff resyncs
+    /// the book
+    pub fn set_sequence_check(&mut self, sequence_check: SequenceCheck) {
+        self.sequence_check = sequence_check;
+        self.book.sequence_check = sequence_check;
+    }
+
+    /// Run the full `LiveOrderBook::check_consistency` after every diff rather than only
+    /// `check_crossed`. Off by default, as it goes through every level of the book.
+    pub fn set_full_consistency_check(&mut self, full_check: bool) {
+        self.full_check = full_check;
+    }
+
+    /// Skip diffs that were already applied, see `BitstampEventStream::set_dedup`
+    pub fn set_dedup(&mut self, window: Duration) {
+        self.stream.set_dedup(window);
+    }
+
+    /// Wait for the next diff and apply it.
+    ///
+    /// The book is refetched from REST after a reconnect of the stream, a detected gap, a
+    /// diff that fails to apply or one that leaves the book crossed (or inconsistent, see
+    /// `set_full_consistency_check`). Diffs received while the snapshot was loading are
+    /// applied on top of it, those older than the snapshot are skipped.
+    pub async fn next(&mut self) -> Result<&LiveOrderBook, Error> {
+        let channel = EventChannel::DiffOrderBook(self.pair);
+        loop {
+            let step = match self.stream.next_frame().await? {
+                // Diffs skip `Event` parsing, see `DiffDecoder`
+                Frame::Text(json) => match self.decoder.decode(&json, &channel) {
+                    Some(diff) => {
+                        self.stream.seen(&channel);
+                        if self.stream.is_duplicate_diff(&channel, diff.microtimestamp) {
+                            continue;
+                        }
+                        let result = self.book.apply_diff(diff);
+                        self.step(result)
+                    }
+                    None => {
+                        let event = self.stream.parse(&json)?;
+                        self.step_event(&channel, &event)
+                    }
+                },
+                Frame::Event(event) => self.step_event(&channel, &event),
+            };
+            match step {
+                Step::Applied => break,
+                Step::Skipped => continue,
+                Step::Resync => {
+                    self.book = self.fetch_snapshot().await?;
+                    self.resyncs += 1;
+                    break;
+                }
+            }
+        }
+        Ok(&self.book)
+    }
+
+    fn step_event(&mut self, channel: &EventChannel, event: &Event) -> Step {
+        if event.channel != *channel || self.stream.is_duplicate(event) {
+            return Step::Skipped;
+        }
+        match event.event {
+            EventEvent::Reconnected => Step::Resync,
+            EventEvent::Data => {
+                let result = self.book.apply(&event.data);
+                self.step(result)
+            }
+            _ => Step::Skipped,
+        }
+    }
+
+    /// Decide on the outcome of applying a diff
+    fn step(&self, result: Result<ApplyResult, Error>) -> Step {
+        match result {
+            Ok(result) if result.sequence_gap_detected => Step::Resync,
+            Ok(result) if result.applied => {
+                let checked = if self.full_check { self.book.check_consistency() } else { self.book.check_crossed() };
+                match checked {
+                    Ok(()) => Step::Applied,
+                    Err(e) => {
+                        warn!("Inconsistent {} book, resyncing: {}", self.pair, e);
+                        Step::Resync
+                    }
+                }
+            }
+            Ok(_) => Step::Skipped,
+            Err(e) => {
+                warn!("Failed to apply {} diff, resyncing: {}", self.pair, e);
+                Step::Resync
+            }
+        }
+    }
+
+    pub fn book(&self) -> &LiveOrderBook {
+        &self.book
+    }
+
+    /// Close the event stream, see `BitstampEventStream::close`
+    pub async fn close(self) -> Result<(), Error> {
+        self.stream.close().await
+    }
+
+    /// Number of times the book was refetched since it was opened
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs
+    }
+
+    async fn fetch_snapshot(&self) -> Result<LiveOrderBook, Error> {
+        let snapshot = self.client.get_order_book(self.pair.as_str(), None).await?;
+        let book = LiveOrderBook::from_snapshot(&snapshot)?.with_sequence_check(self.sequence_check);
+        Ok(match self.max_gap {
+            Some(max_gap) => book.with_max_gap(max_gap),
+            None => book,
+        })
+    }
+}
+
+/// Single resting order from the `DetailOrderBook` channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetailOrder {
+    pub id: String,
+    pub side: TradeSide,
+    pub price: Decimal,
+    pub amount: Decimal,
+}
+
+/// How an order changed between two `DetailOrderBook` events
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderChange {
+    Added(DetailOrder),
+    Modified { before: DetailOrder, after: DetailOrder },
+    /// Filled, cancelled or moved out of the published depth
+    Removed(DetailOrder),
+}
+
+/// Order level book built from `DetailOrderBook` events.
+///
+/// Every event carries the full published depth with an order id per entry, so each update
+/// replaces the book and is diffed against the previous one by id.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetailOrderBook {
+    timestamp: String,
+    microtimestamp: u64,
+    bids: Vec<DetailOrder>,
+    asks: Vec<DetailOrder>,
+    orders: HashMap<String, DetailOrder>,
+}
+
+impl DetailOrderBook {
+    pub fn from_event(data: &EventData) -> Result<DetailOrderBook, Error> {
+        let mut book = DetailOrderBook::default();
+        book.update(data)?;
+        Ok(book)
+    }
+
+    /// Replace the book with a newer event and return what changed, by order id. Events that
+    /// aren't newer than the book are skipped and change nothing.
+    pub fn update(&mut self, data: &EventData) -> Result<Vec<OrderChange>, Error> {
+        let (timestamp, microtimestamp, bids, asks) = match data {
+            EventData::OrderBook { timestamp, microtimestamp, bids, asks } => (timestamp, microtimestamp, bids, asks),
+            other => return Err(text_error(format!("not a detail order book: {:?}", other))),
+        };
+        let microtimestamp = parse_microtimestamp(microtimestamp)?;
+        if microtimestamp <= self.microtimestamp {
+            debug!("Skipping stale detail book {} (book at {})", microtimestamp, self.microtimestamp);
+            return Ok(Vec::new());
+        }
+        let bids = parse_orders(TradeSide::Buy, bids)?;
+        let asks = parse_orders(TradeSide::Sell, asks)?;
+
+        let mut previous = std::mem::take(&mut self.orders);
+        let mut changes = Vec::new();
+        for order in bids.iter().chain(asks.iter()) {
+            match previous.remove(&order.id) {
+                None => changes.push(OrderChange::Added(order.clone())),
+                Some(before) if before != *order => changes.push(OrderChange::Modified { before, after: order.clone() }),
+                Some(_) => {}
+            }
+            self.orders.insert(order.id.clone(), order.clone());
+        }
+        changes.extend(previous.into_values().map(OrderChange::Removed));
+
+        self.timestamp = timestamp.clone();
+        self.microtimestamp = microtimestamp;
+        self.bids = bids;
+        self.asks = asks;
+        Ok(changes)
+    }
+
+    pub fn order(&self, id: &str) -> Option<&DetailOrder> {
+        self.orders.get(id)
+    }
+
+    /// Number of orders ahead of `id` at the same price, in the order Bitstamp lists them
+    pub fn queue_position(&self, id: &str) -> Option<usize> {
+        let order = self.orders.get(id)?;
+        let side = match order.side {
+            TradeSide::Buy => &self.bids,
+            TradeSide::Sell => &self.asks,
+        };
+        side.iter().filter(|o| o.price == order.price).position(|o| o.id == order.id)
+    }
+
+    pub fn microtimestamp(&self) -> u64 {
+        self.microtimestamp
+    }
+
+    /// Bid orders, best price first
+    pub fn bids(&self) -> &[DetailOrder] {
+        &self.bids
+    }
+
+    /// Ask orders, best price first
+    pub fn asks(&self) -> &[DetailOrder] {
+        &self.asks
+    }
+}
+
+fn parse_orders(side: TradeSide, levels: &[Vec<String>]) -> Result<Vec<DetailOrder>, Error> {
+    levels
+        .iter()
+        .map(|level| {
+            let parsed = PriceLevel::from_strings(level)
+                .map_err(|e| text_error_with_inner(format!("invalid order book level {:?}: {}", level, e), e))?;
+            let id = level.get(2).ok_or_else(|| text_error(format!("order book level {:?} has no order id", level)))?;
+            Ok(DetailOrder { id: id.clone(), side, price: parsed.price, amount: parsed.amount })
+        })
+        .collect()
+}
+
+pub(crate) fn parse_microtimestamp(microtimestamp: &str) -> Result<u64, Error> {
+    microtimestamp
+        .parse::<u64>()
+        .map_err(|e| text_error_with_inner(format!("invalid microtimestamp {:?}: {}", microtimestamp, e), e))
+}
+
+fn update_levels(side: &mut BTreeMap<Decimal, Decimal>, levels: &[Vec<String>]) -> Result<(), Error> {
+    for level in levels {
+        let level = PriceLevel::from_strings(level)
+            .map_err(|e| text_error_with_inner(format!("invalid order book level {:?}: {}", level, e), e))?;
+        if level.amount.is_zero() {
+            side.remove(&level.price);
+        } else {
+            side.insert(level.price, level.amount);
+        }
+    }
+    Ok(())
+}
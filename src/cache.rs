@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+type Slot = Arc<tokio::sync::Mutex<Option<(Instant, Arc<String>)>>>;
+
+/// Short lived cache of raw REST replies.
+///
+/// Concurrent lookups of the same key wait on one fetch instead of each sending a request.
+/// Failed fetches aren't cached, the next waiter tries again.
+pub(crate) struct ResponseCache {
+    ttl: Duration,
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(ttl: Duration) -> ResponseCache {
+        ResponseCache { ttl, slots: Mutex::new(HashMap::new()) }
+    }
+
+    pub(crate) async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<Arc<String>, Error>
+        where
+            F: FnOnce() -> Fut,
+            Fut: Future<Output = Result<String, Error>>,
+    {
+        let slot = self.slots.lock().unwrap().entry(key.to_string()).or_default().clone();
+        // Held across the fetch, so callers arriving meanwhile wait for its result
+        let mut entry = slot.lock().await;
+        if let Some((fetched, reply)) = entry.as_ref() {
+            if fetched.elapsed() < self.ttl {
+                return Ok(reply.clone());
+            }
+        }
+        let reply = Arc::new(fetch().await?);
+        *entry = Some((Instant::now(), reply.clone()));
+        Ok(reply)
+    }
+}
@@ -0,0 +1,401 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Maximum number of fractional digits kept by a division.
+const DIV_SCALE: u32 = 20;
+
+/// Most fractional digits a parsed number may have, excluding trailing zeros.
+const MAX_SCALE: u32 = 28;
+
+/// Exact base-10 number used for prices, amounts and fees.
+///
+/// Stored as an integer mantissa and a decimal scale, so `"0.1"` is `1 * 10^-1` and
+/// arithmetic never goes through binary floating point. Serialized as a string, the way
+/// Bitstamp sends numeric values.
+#[derive(Clone, Copy, Default)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseDecimalError(String);
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid decimal: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+/// Largest power of ten an i128 holds, any mantissa divided by more than that is zero.
+const MAX_POW10: u32 = 38;
+
+fn pow10(exp: u32) -> i128 {
+    10i128.checked_pow(exp).expect("Decimal overflow")
+}
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal { mantissa: 0, scale: 0 };
+    pub const ONE: Decimal = Decimal { mantissa: 1, scale: 0 };
+
+    /// `mantissa * 10^-scale`, rounded to 28 decimal places when the scale is larger
+    pub fn new(mantissa: i128, scale: u32) -> Self {
+        Decimal { mantissa, scale }.round_dp(MAX_SCALE)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn is_sign_negative(&self) -> bool {
+        self.mantissa < 0
+    }
+
+    pub fn is_sign_positive(&self) -> bool {
+        self.mantissa > 0
+    }
+
+    pub fn abs(&self) -> Self {
+        Decimal { mantissa: self.mantissa.abs(), scale: self.scale }
+    }
+
+    /// Drop trailing fractional zeros, `1.500` becomes `1.5`
+    pub fn normalize(&self) -> Self {
+        let mut d = *self;
+        while d.scale > 0 && d.mantissa % 10 == 0 {
+            d.mantissa /= 10;
+            d.scale -= 1;
+        }
+        if d.mantissa == 0 {
+            d.scale = 0;
+        }
+        d
+    }
+
+    /// Cut off digits beyond `dp` decimal places, rounding toward zero
+    pub fn trunc(&self, dp: u32) -> Self {
+        if self.scale <= dp {
+            return *self;
+        }
+        if self.scale - dp > MAX_POW10 {
+            return Decimal { mantissa: 0, scale: dp };
+        }
+        Decimal { mantissa: self.mantissa / pow10(self.scale - dp), scale: dp }
+    }
+
+    /// Round to `dp` decimal places, halves rounded away from zero
+    pub fn round_dp(&self, dp: u32) -> Self {
+        if self.scale <= dp {
+            return *self;
+        }
+        // Not even half of the divisor
+        if self.scale - dp > MAX_POW10 {
+            return Decimal { mantissa: 0, scale: dp };
+        }
+        let divisor = pow10(self.scale - dp);
+        let mut mantissa = self.mantissa / divisor;
+        let remainder = (self.mantissa % divisor).abs();
+        if remainder >= divisor - remainder {
+            mantissa += self.mantissa.signum();
+        }
+        Decimal { mantissa, scale: dp }
+    }
+
+    fn rescale(&self, scale: u32) -> Option<i128> {
+        self.mantissa.checked_mul(10i128.checked_pow(scale - self.scale)?)
+    }
+
+    /// Both mantissas at the larger of the two scales, `None` if that overflows
+    fn aligned(a: &Decimal, b: &Decimal) -> Option<(i128, i128, u32)> {
+        let scale = a.scale.max(b.scale);
+        Some((a.rescale(scale)?, b.rescale(scale)?, scale))
+    }
+
+    /// Compare absolute values digit by digit, for when aligning the scales would overflow
+    fn cmp_digits(a: &Decimal, b: &Decimal) -> Ordering {
+        let da = a.mantissa.unsigned_abs().to_string();
+        let db = b.mantissa.unsigned_abs().to_string();
+        // Position of the leading digit relative to the decimal point
+        let ea = da.len() as i64 - a.scale as i64;
+        let eb = db.len() as i64 - b.scale as i64;
+        ea.cmp(&eb).then_with(|| {
+            let len = da.len().max(db.len());
+            let pa = da.bytes().chain(std::iter::repeat(b'0')).take(len);
+            let pb = db.bytes().chain(std::iter::repeat(b'0')).take(len);
+            pa.cmp(pb)
+        })
+    }
+
+    /// `None` on overflow
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = Decimal::aligned(&self, &other)?;
+        Some(Decimal { mantissa: a.checked_add(b)?, scale })
+    }
+
+    /// `None` on overflow
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        let (a, b, scale) = Decimal::aligned(&self, &other)?;
+        Some(Decimal { mantissa: a.checked_sub(b)?, scale })
+    }
+
+    /// `None` on overflow, rounded to 28 decimal places
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        let a = self.normalize();
+        let b = other.normalize();
+        let product = Decimal { mantissa: a.mantissa.checked_mul(b.mantissa)?, scale: a.scale.checked_add(b.scale)? };
+        Some(product.round_dp(MAX_SCALE))
+    }
+
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let a = self.normalize();
+        let b = other.normalize();
+        // a / b = (ma / mb) * 10^(sb - sa), so take enough quotient digits of ma / mb
+        // for the result to end up with DIV_SCALE fractional digits
+        let digits = (DIV_SCALE + b.scale).saturating_sub(a.scale);
+        let divisor = b.mantissa.unsigned_abs();
+        let mut quotient = a.mantissa.unsigned_abs() / divisor;
+        let mut remainder = a.mantissa.unsigned_abs() % divisor;
+        for _ in 0..digits {
+            remainder = remainder.checked_mul(10)?;
+            quotient = quotient.checked_mul(10)?.checked_add(remainder / divisor)?;
+            remainder %= divisor;
+        }
+        let mut mantissa = i128::try_from(quotient).ok()?;
+        if a.mantissa.signum() != b.mantissa.signum() {
+            mantissa = -mantissa;
+        }
+        let scale = digits + a.scale;
+        let result = if scale >= b.scale {
+            Decimal { mantissa, scale: scale - b.scale }
+        } else {
+            Decimal { mantissa: mantissa.checked_mul(10i128.checked_pow(b.scale - scale)?)?, scale: 0 }
+        };
+        Some(result.normalize())
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    /// Accepts plain (`"0.00000001"`) and scientific (`"1e-8"`, `"1.5E+3"`) notation. Fails
+    /// for more than 28 significant fractional digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseDecimalError(s.to_string());
+        let trimmed = s.trim();
+        let (number, exponent) = match trimmed.find(['e', 'E']) {
+            Some(i) => (&trimmed[..i], trimmed[i + 1..].parse::<i32>().map_err(|_| err())?),
+            None => (trimmed, 0),
+        };
+        let (negative, digits) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (int_part, frac_part) = match digits.find('.') {
+            Some(i) => (&digits[..i], &digits[i + 1..]),
+            None => (digits, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(err());
+        }
+        let mut mantissa: i128 = 0;
+        for c in int_part.chars().chain(frac_part.chars()) {
+            let digit = c.to_digit(10).ok_or_else(err)?;
+            mantissa = mantissa.checked_mul(10).and_then(|m| m.checked_add(digit as i128)).ok_or_else(err)?;
+        }
+        if negative {
+            mantissa = -mantissa;
+        }
+        let mut scale = frac_part.len() as i64 - exponent as i64;
+        while scale > MAX_SCALE as i64 && mantissa != 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        if scale > MAX_SCALE as i64 {
+            return if mantissa == 0 { Ok(Decimal::ZERO) } else { Err(err()) };
+        }
+        if scale >= 0 {
+            Ok(Decimal { mantissa, scale: scale as u32 })
+        } else {
+            let factor = u32::try_from(-scale).ok().and_then(|e| 10i128.checked_pow(e)).ok_or_else(err)?;
+            Ok(Decimal { mantissa: mantissa.checked_mul(factor).ok_or_else(err)?, scale: 0 })
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let padded = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if let Some((a, b, _)) = Decimal::aligned(self, other) {
+            return a.cmp(&b);
+        }
+        let sign = self.mantissa.signum().cmp(&other.mantissa.signum());
+        if sign != Ordering::Equal || self.mantissa == 0 {
+            return sign;
+        }
+        let magnitude = Decimal::cmp_digits(self, other);
+        if self.mantissa < 0 {
+            magnitude.reverse()
+        } else {
+            magnitude
+        }
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let n = self.normalize();
+        n.mantissa.hash(state);
+        n.scale.hash(state);
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Decimal { mantissa: value as i128, scale: 0 }
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(value: u64) -> Self {
+        Decimal { mantissa: value as i128, scale: 0 }
+    }
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+
+    /// Panics on overflow, use `checked_add` to handle it
+    fn add(self, other: Decimal) -> Decimal {
+        self.checked_add(other).expect("Decimal overflow")
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Decimal;
+
+    /// Panics on overflow, use `checked_sub` to handle it
+    fn sub(self, other: Decimal) -> Decimal {
+        self.checked_sub(other).expect("Decimal overflow")
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+
+    /// Panics on overflow, use `checked_mul` to handle it
+    fn mul(self, other: Decimal) -> Decimal {
+        self.checked_mul(other).expect("Decimal overflow")
+    }
+}
+
+impl Div for Decimal {
+    type Output = Decimal;
+
+    /// Panics on division by zero, use `checked_div` to handle it
+    fn div(self, other: Decimal) -> Decimal {
+        self.checked_div(other).expect("Decimal division by zero or overflow")
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+
+    fn neg(self) -> Decimal {
+        Decimal { mantissa: -self.mantissa, scale: self.scale }
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        struct DecimalVisitor;
+        impl<'de> de::Visitor<'de> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a decimal number or string")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+                Decimal::from_str(s).map_err(de::Error::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(Decimal::from(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(Decimal::from(v))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                Decimal::from_str(v.to_string().as_str()).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
@@ -33,12 +33,65 @@ impl Error {
     pub fn status(&self) -> Option<StatusCode> {
         match self.inner.kind.clone() {
             Kind::Status(code) => Some(code),
-            Kind::ErrorV1(code, _0) => Some(code),
-            Kind::ErrorV2(code, _0, _1) => Some(code),
+            Kind::ErrorV1(code, _) => Some(code),
+            Kind::ErrorV2(code, _, _) => code,
             _ => None,
         }
     }
 
+    /// Returns true if the request never got a response, `source()` then holds the
+    /// underlying transport error (a `hyper::Error` with the default transport).
+    pub fn is_transport(&self) -> bool {
+        matches!(self.inner.kind, Kind::Transport(_))
+    }
+
+    /// Returns true if the request didn't complete within the configured timeout
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.inner.kind, Kind::Timeout(_))
+    }
+
+    /// Returns true if Bitstamp rejected the credentials or token, e.g. a private channel
+    /// subscription with an expired WebSocket token
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self.inner.kind, Kind::Unauthorized(_))
+    }
+
+    /// Returns true if a post-only limit order was rejected because it would have matched
+    /// immediately. Reprice it further from the market and try again.
+    pub fn is_post_only_rejected(&self) -> bool {
+        matches!(self.inner.kind, Kind::PostOnlyRejected(_))
+    }
+
+    /// Returns true if the event stream lost its connection for good, e.g. after running out
+    /// of reconnect attempts. `source()` holds the last connection error.
+    pub fn is_websocket(&self) -> bool {
+        matches!(self.inner.kind, Kind::WebSocket(_))
+    }
+
+    /// Bitstamp's error code (e.g. `API0005`), classified, for V2 error replies
+    pub fn code(&self) -> Option<BitstampErrorCode> {
+        match self.inner.kind {
+            Kind::ErrorV2(_, _, ref code) if !code.is_empty() => Some(BitstampErrorCode::from_code(code)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn kind(&self) -> &Kind {
+        &self.inner.kind
+    }
+
+    /// Whether the request may succeed if sent again: transport failures and server errors
+    pub(crate) fn is_retryable(&self) -> bool {
+        match self.inner.kind.clone() {
+            Kind::Transport(_) | Kind::Timeout(_) => true,
+            Kind::Status(code) | Kind::ErrorV1(code, _) | Kind::ErrorV2(Some(code), _, _) => {
+                code.is_server_error() || code == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+
+    #[allow(dead_code)]
     pub(crate) fn with_prefix<E: std::fmt::Display>(mut self, prefix: E) -> Error {
         self.inner.description = format!("{}{}", prefix, self.inner.description);
         self
@@ -49,7 +102,7 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.inner.description)?;
         match self.inner.kind.clone() {
-            Kind::Text(ref text) => {
+            Kind::Text(ref text) | Kind::Transport(ref text) | Kind::WebSocket(ref text) => {
                 write!(f, "{}", text)?;
             }
             Kind::Status(ref code) => {
@@ -68,7 +121,7 @@ impl fmt::Display for Error {
                 };
                 write!(f, "{} ({}) - {}", prefix, code, error)?;
             }
-            Kind::ErrorV2(code, error, error_code) => {
+            Kind::ErrorV2(Some(code), error, error_code) => {
                 let prefix = if code.is_client_error() {
                     "HTTP status client error"
                 } else {
@@ -76,6 +129,21 @@ impl fmt::Display for Error {
                 };
                 write!(f, "{} ({}) - {} ({})", prefix, code, error, error_code)?;
             }
+            Kind::ErrorV2(None, error, error_code) => {
+                write!(f, "Bitstamp error - {} ({})", error, error_code)?;
+            }
+            Kind::Unauthorized(ref message) => {
+                write!(f, "unauthorized: {}", message)?;
+            }
+            Kind::PostOnlyRejected(ref reason) => {
+                write!(f, "post-only order would have taken liquidity: {}", reason)?;
+            }
+            Kind::Timeout(timeout) => {
+                write!(f, "request timed out after {:?}", timeout)?;
+            }
+            Kind::ResponseTooLarge(limit) => {
+                write!(f, "response body exceeded {} bytes", limit)?;
+            }
         };
 
         Ok(())
@@ -84,7 +152,7 @@ impl fmt::Display for Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut builder = f.debug_struct("reqwest::Error");
+        let mut builder = f.debug_struct("bitstamp::Error");
 
         builder.field("kind", &self.inner.kind);
 
@@ -103,12 +171,56 @@ impl StdError for Error {
     }
 }
 
+/// Bitstamp V2 error codes callers may want to react to, see `Error::code`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BitstampErrorCode {
+    /// `API0001`, the key doesn't exist or was revoked. Fatal.
+    InvalidApiKey,
+    /// `API0002`, the key lacks the permission for this call
+    NoPermission,
+    /// `API0004`, the nonce was already used. The request didn't execute and is resent
+    /// once with a fresh nonce automatically.
+    InvalidNonce,
+    /// `API0005`, the signature doesn't match, usually a wrong secret. Fatal.
+    InvalidSignature,
+    /// `API0012`, a maker-or-cancel (post-only) order would have matched immediately
+    MakerOrCancelRejected,
+    /// `API0020`, an order with this `client_order_id` was already placed
+    DuplicateClientOrderId,
+    Other(String),
+}
+
+impl BitstampErrorCode {
+    pub fn from_code(code: &str) -> BitstampErrorCode {
+        match code {
+            "API0001" => BitstampErrorCode::InvalidApiKey,
+            "API0002" => BitstampErrorCode::NoPermission,
+            "API0004" => BitstampErrorCode::InvalidNonce,
+            "API0005" => BitstampErrorCode::InvalidSignature,
+            "API0012" => BitstampErrorCode::MakerOrCancelRejected,
+            "API0020" => BitstampErrorCode::DuplicateClientOrderId,
+            other => BitstampErrorCode::Other(other.to_string()),
+        }
+    }
+
+    /// The credentials are wrong and retrying won't help
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, BitstampErrorCode::InvalidApiKey | BitstampErrorCode::InvalidSignature)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Kind {
     Text(String),
+    Transport(String),
     Status(StatusCode),
     ErrorV1(StatusCode, String),
-    ErrorV2(StatusCode, String, String),
+    ErrorV2(Option<StatusCode>, String, String),
+    ResponseTooLarge(usize),
+    Timeout(std::time::Duration),
+    Unauthorized(String),
+    PostOnlyRejected(String),
+    WebSocket(String),
 }
 
 pub(crate) fn text_error(message: String) -> Error {
@@ -119,6 +231,10 @@ pub(crate) fn text_error_with_inner<E: Into<BoxError>>(message: String, e: E) ->
     Error::new(Kind::Text(message), Some(e))
 }
 
+pub(crate) fn transport_error<E: Into<BoxError>>(message: String, e: E) -> Error {
+    Error::new(Kind::Transport(message), Some(e))
+}
+
 pub(crate) fn status_code(status: StatusCode) -> Error {
     Error::new(Kind::Status(status), None::<Error>)
 }
@@ -127,7 +243,26 @@ pub(crate) fn v1_error(status: StatusCode, error: String) -> Error {
     Error::new(Kind::ErrorV1(status, error), None::<Error>)
 }
 
-pub(crate) fn v2_error(status: StatusCode, error: String, error_code: String) -> Error {
+pub(crate) fn v2_error(status: Option<StatusCode>, error: String, error_code: String) -> Error {
     Error::new(Kind::ErrorV2(status, error, error_code), None::<Error>)
 }
 
+pub(crate) fn timeout(timeout: std::time::Duration) -> Error {
+    Error::new(Kind::Timeout(timeout), None::<Error>)
+}
+
+pub(crate) fn response_too_large(limit: usize) -> Error {
+    Error::new(Kind::ResponseTooLarge(limit), None::<Error>)
+}
+
+pub(crate) fn unauthorized(message: String) -> Error {
+    Error::new(Kind::Unauthorized(message), None::<Error>)
+}
+
+pub(crate) fn websocket_error<E: Into<BoxError>>(message: String, e: E) -> Error {
+    Error::new(Kind::WebSocket(message), Some(e))
+}
+
+pub(crate) fn post_only_rejected(reason: String) -> Error {
+    Error::new(Kind::PostOnlyRejected(reason), None::<Error>)
+}
@@ -0,0 +1,20 @@
+use serde::de::{DeserializeOwned, DeserializeSeed};
+
+/// Error of the JSON backend in use
+pub(crate) type DecodeError = serde_json::Error;
+
+/// Decode a REST reply or stream message.
+///
+/// All parsing of exchange data goes through here, so a faster backend (e.g. SIMD based)
+/// only needs to be swapped in at this one place, behind a feature.
+pub(crate) fn from_str<T: DeserializeOwned>(json: &str) -> Result<T, DecodeError> {
+    serde_json::from_str(json)
+}
+
+/// Decode into state owned by `seed`, for hot paths that reuse their buffers
+pub(crate) fn from_str_seed<'de, S: DeserializeSeed<'de>>(json: &'de str, seed: S) -> Result<S::Value, DecodeError> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let value = seed.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
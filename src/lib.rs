@@ -1,14 +1,28 @@
 #[macro_use]
 extern crate log;
 
+pub mod book;
+mod cache;
+mod decimal;
 mod error;
+mod json;
+mod limiter;
+pub mod nonce;
+mod stream;
+pub mod transport;
 pub mod types;
 
-use crate::error::{Error, status_code, text_error, text_error_with_inner, v2_error, v1_error};
+pub use crate::error::{BitstampErrorCode, Error};
+pub use crate::stream::{BitstampEventStream, EventStreamHandle, RawHook, TradeStream, WStream};
+
+use crate::error::{Kind, post_only_rejected, response_too_large, status_code, text_error, text_error_with_inner, timeout, transport_error, unauthorized, v2_error, v1_error};
 
 use serde::{de::DeserializeOwned, Serialize};
-use hyper::{body::HttpBody, client::HttpConnector, Body, Client, Request};
+use futures::StreamExt;
+use hyper::{body::HttpBody, Body, Client, Request, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
 use hyper_tls::HttpsConnector;
+pub use hyper_tls::native_tls;
 
 extern crate hmac;
 extern crate sha2;
@@ -16,198 +30,932 @@ extern crate sha2;
 use sha2::Sha256;
 use hmac::{Hmac, Mac, NewMac};
 use std::str;
-use uuid::Uuid;
-use crate::types::Time;
-use std::collections::HashMap;
-use std::str::FromStr;
-use chrono::Timelike;
-use std::time::Duration;
-use tokio::net::TcpStream;
-use tokio_tls::TlsStream;
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
-use tokio_tungstenite::tungstenite::Message;
-use futures_util::{SinkExt, StreamExt};
-
-const REST_HOST_PREFIX: &str = "www.bitstamp.net/api/v2";
+use crate::nonce::{NonceGenerator, UuidNonceGenerator};
+use crate::transport::Transport;
+use crate::types::{Decimal, Time};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::stream::StreamConfig;
+use crate::cache::ResponseCache;
+use crate::limiter::RateLimiter;
+
+const REST_HOST: &str = "www.bitstamp.net";
+const WS_URL: &str = "wss://ws.bitstamp.net";
+
+/// Version of Bitstamp's REST API an endpoint belongs to. Everything the crate covers is v2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// Where the endpoints live, relative to the host
+    fn path_prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "api",
+            ApiVersion::V2 => "api/v2",
+        }
+    }
+
+    /// `X-Auth-Version` of the signature. Bitstamp signs requests to either version with its
+    /// v2 scheme, the v1 one (a signed nonce in the body) is retired.
+    fn auth_version(self) -> &'static str {
+        match self {
+            ApiVersion::V1 | ApiVersion::V2 => "v2",
+        }
+    }
+}
+const DEFAULT_MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
+/// Orders `place_orders` sends at once, to stay clear of the rate limit
+pub const MAX_CONCURRENT_ORDERS: usize = 4;
 
 type HmacSha256 = Hmac<Sha256>;
-pub type WStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
-type WebClient = Client<HttpsConnector<HttpConnector>, Body>;
 
+#[derive(Clone)]
 pub struct Bitstamp {
-    client: WebClient,
+    transport: Arc<dyn Transport>,
     secret: String,
     key: String,
+    nonce_generator: Arc<dyn NonceGenerator>,
+    max_response_bytes: usize,
+    max_retries: u32,
+    retry_backoff: Duration,
+    request_timeout: Option<Duration>,
+    stream_config: StreamConfig,
+    public_cache: Option<Arc<ResponseCache>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    websocket_token: Arc<tokio::sync::Mutex<Option<(Instant, types::WebsocketToken)>>>,
+    pairs_info: Arc<Mutex<HashMap<String, types::PairInfo>>>,
 }
 
-pub struct BitstampEventStream {
-    ws_stream: WStream,
-    timeout: Duration,
+/// Credentials are redacted, only the first 4 characters of the key are shown
+impl std::fmt::Debug for Bitstamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let key: String = self.key.chars().take(4).collect();
+        f.debug_struct("Bitstamp")
+            .field("key", &format!("{}***", key))
+            .field("secret", &"***")
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("max_retries", &self.max_retries)
+            .field("retry_backoff", &self.retry_backoff)
+            .field("request_timeout", &self.request_timeout)
+            .finish_non_exhaustive()
+    }
 }
 
-impl BitstampEventStream {
-    pub async fn next(&mut self) -> Result<types::Event, String> {
-        loop {
-            let next = self.ws_stream.next();
-            match tokio::time::timeout(self.timeout, next).await {
-                // Timed out
-                Err(_) => return Err(format!("no activity for at least {:?}", self.timeout).to_string()),
-                // Didn't time out
-                Ok(next_result) => match next_result {
-                    Some(msg) => match msg {
-                        Ok(msg) => {
-                            match self.handle_message(msg).await {
-                                Ok(maybe_msg) => {
-                                    if let Some(msg) = maybe_msg {
-                                        return Ok(msg);
-                                    } else {
-                                        // Ignore other messages (but they'll reset the timeout)
-                                        continue;
-                                    }
-                                }
-                                Err(e) => return Err(e),
-                            };
-                        }
-                        Err(e) => return Err(e.to_string()),
-                    },
-                    None => continue,
-                },
-            }
+pub struct BitstampBuilder {
+    secret: String,
+    key: String,
+    nonce_generator: Arc<dyn NonceGenerator>,
+    max_response_bytes: usize,
+    max_retries: u32,
+    retry_backoff: Duration,
+    request_timeout: Option<Duration>,
+    public_cache_ttl: Option<Duration>,
+    rate_limit: Option<u32>,
+    transport: Option<Arc<dyn Transport>>,
+    stream_config: StreamConfig,
+    prewarm: bool,
+    #[cfg(feature = "danger-insecure-tls")]
+    accept_invalid_certs: bool,
+}
+
+impl BitstampBuilder {
+    pub fn new(secret: String, key: String) -> Self {
+        BitstampBuilder {
+            secret,
+            key,
+            nonce_generator: Arc::new(UuidNonceGenerator),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            max_retries: 0,
+            retry_backoff: Duration::from_millis(250),
+            request_timeout: None,
+            public_cache_ttl: None,
+            rate_limit: None,
+            transport: None,
+            stream_config: StreamConfig {
+                url: WS_URL.to_string(),
+                tls_connector: None,
+                timeout: Duration::from_secs(20),
+                write_timeout: Duration::from_secs(10),
+                auto_reconnect: false,
+                reconnect_delay: Duration::from_secs(1),
+                max_reconnect_attempts: None,
+                ws_config: WebSocketConfig::default(),
+            },
+            prewarm: false,
+            #[cfg(feature = "danger-insecure-tls")]
+            accept_invalid_certs: false,
         }
     }
 
-    async fn handle_message(&self, msg: Message) -> Result<Option<types::Event>, String> {
-        match msg {
-            Message::Binary(bytes) => match String::from_utf8(bytes) {
-                Ok(json) => {
-                    let json = json.as_str();
-                    let event: Result<types::Event, _> = serde_json::from_str(json);
-                    match event {
-                        Ok(event) => Ok(Some(event)),
-                        Err(e) => {
-                            warn!("Couldn't deserialize: {:?}.  Original JSON:\n{}", e, &json);
-                            Err(format!("unable to deserialize: {}", e))
-                        }
-                    }
-                }
-                Err(e) => Err(format!("UTF-8 decode failed: {}", e)),
-            },
-            Message::Text(t) => {
-                let event: Result<types::Event, _> = serde_json::from_str(t.as_str());
-                match event {
-                    Ok(event) => Ok(Some(event)),
-                    Err(e) => {
-                        warn!("Couldn't deserialize: {:?}.  Original JSON:\n{}", e, &t);
-                        Err(format!("unable to deserialize: {}", e))
-                    }
-                }
+    /// Use a custom nonce source for signed requests instead of random UUIDs
+    pub fn nonce_generator<N: NonceGenerator + 'static>(mut self, nonce_generator: N) -> Self {
+        self.nonce_generator = Arc::new(nonce_generator);
+        self
+    }
+
+    /// Maximum size of a REST response body, larger replies fail with an error (default 8 MiB)
+    pub fn max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Retry GETs (and orders carrying a `client_order_id`) up to `max_retries` times on
+    /// transport failures, 5xx and 429 replies. Other POSTs are never retried. Default 0.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled for every further attempt (default 250ms)
+    pub fn retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Give up on a REST request (including reading the reply) after `timeout`, each retry
+    /// gets the full timeout again. No timeout by default.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Cache `get_order_book`, `get_transactions` and `get_pairs_info_map` replies for `ttl`,
+    /// per pair and query. Concurrent identical calls then share a single request, useful for
+    /// dashboards fanning out over the same data. Off by default.
+    pub fn public_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.public_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Pace REST requests to at most `requests_per_second`, with bursts of up to a second's
+    /// worth. Requests over the budget wait instead of failing. The budget is shared by all
+    /// clones of the client. Off by default, Bitstamp allows 400 requests a second.
+    pub fn rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.rate_limit = Some(requests_per_second);
+        self
+    }
+
+    /// Send REST requests through a custom transport instead of the default HTTPS client
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// WebSocket endpoint, e.g. a local mock server in tests (default `wss://ws.bitstamp.net`)
+    pub fn ws_url(mut self, url: &str) -> Self {
+        self.stream_config.url = url.to_string();
+        self
+    }
+
+    /// TLS configuration for the WebSocket stream, e.g. extra root certificates for a
+    /// TLS-intercepting proxy. The system defaults are used otherwise.
+    pub fn ws_tls_connector(mut self, connector: native_tls::TlsConnector) -> Self {
+        self.stream_config.tls_connector = Some(connector);
+        self
+    }
+
+    /// How long the event stream may stay silent before `next()` fails (default 20s)
+    pub fn ws_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_config.timeout = timeout;
+        self
+    }
+
+    /// How long sending a frame (e.g. a subscription) may take before it fails with an error
+    /// for which `Error::is_timeout` is true (default 10s)
+    pub fn ws_write_timeout(mut self, timeout: Duration) -> Self {
+        self.stream_config.write_timeout = timeout;
+        self
+    }
+
+    /// Reopen dropped event stream connections and resubscribe their channels (default off)
+    pub fn ws_auto_reconnect(mut self, auto_reconnect: bool) -> Self {
+        self.stream_config.auto_reconnect = auto_reconnect;
+        self
+    }
+
+    /// Delay before the second reconnect attempt, doubled after each further failure up to 30s
+    /// (default 1s). The first attempt is made right away.
+    pub fn ws_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.stream_config.reconnect_delay = delay;
+        self
+    }
+
+    /// Give up after this many consecutive failed reconnect attempts, `next()` then fails with
+    /// an error for which `Error::is_websocket` is true (default unlimited)
+    pub fn ws_max_reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.stream_config.max_reconnect_attempts = Some(attempts);
+        self
+    }
+
+    /// Largest WebSocket message the stream accepts, a bigger one drops the connection
+    /// (default 64 MiB). Only worth raising for snapshots of unusually deep books, e.g. on
+    /// `DetailOrderBook`.
+    pub fn ws_max_message_size(mut self, bytes: usize) -> Self {
+        self.stream_config.ws_config.max_message_size = Some(bytes);
+        self
+    }
+
+    /// Largest single WebSocket frame the stream accepts, a bigger one drops the connection
+    /// (default 16 MiB). Raise it together with `ws_max_message_size`.
+    pub fn ws_max_frame_size(mut self, bytes: usize) -> Self {
+        self.stream_config.ws_config.max_frame_size = Some(bytes);
+        self
+    }
+
+    /// Open the REST connection in the background as part of `build()` so the first real
+    /// request doesn't pay for the TLS handshake. Needs to be called inside a tokio runtime,
+    /// use `build_warm()` to wait for the connection instead.
+    pub fn prewarm(mut self, prewarm: bool) -> Self {
+        self.prewarm = prewarm;
+        self
+    }
+
+    /// **DANGER**: skip TLS certificate and hostname verification on the REST client and the
+    /// WebSocket stream. Anyone on the network path can then read and alter requests, including
+    /// signed ones. Only for testing against a mock server with a self-signed certificate, which
+    /// is why it needs the non-default `danger-insecure-tls` feature.
+    ///
+    /// Ignored for REST when a custom `transport` is set, and for the stream when a
+    /// `ws_tls_connector` is set.
+    #[cfg(feature = "danger-insecure-tls")]
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Build the client and wait until the REST connection is open
+    pub async fn build_warm(mut self) -> Bitstamp {
+        self.prewarm = false;
+        let bts = self.build();
+        bts.warm_up().await;
+        bts
+    }
+
+    pub fn build(self) -> Bitstamp {
+        #[allow(unused_mut)]
+        let mut stream_config = self.stream_config;
+        #[cfg(feature = "danger-insecure-tls")]
+        let insecure_tls = if self.accept_invalid_certs {
+            warn!("TLS certificate verification is disabled");
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .expect("failed to build TLS connector");
+            if stream_config.tls_connector.is_none() {
+                stream_config.tls_connector = Some(connector.clone());
             }
-            Message::Ping(_) => {
-                debug!("Ping!");
-                Ok(None)
+            Some(connector)
+        } else {
+            None
+        };
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            #[cfg(feature = "danger-insecure-tls")]
+            None if insecure_tls.is_some() => {
+                let mut http = hyper::client::HttpConnector::new();
+                http.enforce_http(false);
+                let https = HttpsConnector::from((http, tokio_native_tls::TlsConnector::from(insecure_tls.unwrap())));
+                Arc::new(Client::builder().build::<_, hyper::Body>(https))
             }
-            Message::Close(t) => {
-                debug!("close: {:?}", t);
-                Ok(None)
+            None => {
+                let https = HttpsConnector::new();
+                Arc::new(Client::builder().build::<_, hyper::Body>(https))
             }
-            Message::Pong(_) => {
-                debug!("Pong!");
-                Ok(None)
+        };
+
+        let prewarm = self.prewarm;
+        let bts = Bitstamp {
+            transport,
+            secret: self.secret,
+            key: self.key,
+            nonce_generator: self.nonce_generator,
+            max_response_bytes: self.max_response_bytes,
+            max_retries: self.max_retries,
+            retry_backoff: self.retry_backoff,
+            request_timeout: self.request_timeout,
+            stream_config,
+            public_cache: self.public_cache_ttl.map(|ttl| Arc::new(ResponseCache::new(ttl))),
+            rate_limiter: self.rate_limit.map(|rate| Arc::new(RateLimiter::new(rate))),
+            websocket_token: Arc::new(tokio::sync::Mutex::new(None)),
+            pairs_info: Arc::new(Mutex::new(HashMap::new())),
+        };
+        if prewarm {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    let warm = bts.clone();
+                    handle.spawn(async move { warm.warm_up().await });
+                }
+                Err(_) => warn!("prewarm requested outside of a tokio runtime, skipping"),
             }
         }
+        bts
     }
+}
+
+/// Per call overrides of the client's timeout and retry settings, for the `*_with_options`
+/// methods. Unset fields use the client's configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    pub timeout: Option<Duration>,
+    /// Only used for requests that are safe to retry, see `BitstampBuilder::max_retries`
+    pub max_retries: Option<u32>,
+}
 
-    pub async fn subscribe(&mut self, channel: types::EventChannel) {
-        self.ws_stream.send(Message::Text(serde_json::to_string(&types::OutEvent { event: types::EventEvent::BtsSubscribe, data: types::OutEventData { channel } }).unwrap())).await;
+impl RequestOptions {
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
-    pub async fn unsubscribe(&mut self, channel: types::EventChannel) {
-        self.ws_stream.send(Message::Text(serde_json::to_string(&types::OutEvent { event: types::EventEvent::BtsUnsubscribe, data: types::OutEventData { channel } }).unwrap())).await;
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
     }
 }
 
 impl Bitstamp {
     pub fn new(secret: String, key: String) -> Self {
-        let https = HttpsConnector::new();
-        let client = Client::builder().build::<_, hyper::Body>(https);
+        BitstampBuilder::new(secret, key).build()
+    }
 
-        let mut bts = Bitstamp {
-            client,
-            secret,
-            key,
-        };
-        bts
+    pub fn builder(secret: String, key: String) -> BitstampBuilder {
+        BitstampBuilder::new(secret, key)
     }
 
     pub async fn event_stream(&self) -> Result<BitstampEventStream, Error> {
-        let url = "wss://ws.bitstamp.net";
-        match connect_async(url.clone()).await {
-            Ok((mut ws_stream, _response)) => {
-                debug!("Connected to {}", url);
+        BitstampEventStream::connect(self.stream_config.clone()).await
+    }
 
-                let timeout = Duration::from_secs(20);
-                return Ok(BitstampEventStream { ws_stream, timeout });
+    /// The last `backfill` trades of a pair, followed seamlessly by live ones. The channel is
+    /// subscribed before the history is fetched and trades seen in both are returned once.
+    /// History goes back at most a day.
+    pub async fn trades_with_backfill(&self, pair: types::CurrencyPairs, backfill: usize) -> Result<TradeStream, Error> {
+        let mut stream = self.event_stream().await?;
+        stream.subscribe(types::EventChannel::LiveTrades(pair)).await?;
+        let mut trades = Vec::new();
+        if backfill > 0 {
+            trades = self.get_transactions(pair.as_str(), Some(Time::Hour)).await?;
+            if trades.len() < backfill {
+                trades = self.get_transactions(pair.as_str(), Some(Time::Day)).await?;
             }
-            Err(e) => {
-                warn!("Failed to connect to {:?}: {:?}", url, e);
-                return Err(text_error(format!("Failed to connect to {:?}: {:?}", url, e)));
-            }
-        };
+        }
+        // Newest first from REST
+        trades.truncate(backfill);
+        trades.reverse();
+        Ok(TradeStream::new(stream, pair, trades))
+    }
+
+    /// Live order book for a pair: subscribes to its diff channel, loads a REST snapshot and
+    /// keeps it current, resyncing after reconnects and gaps. Enable `ws_auto_reconnect` for
+    /// the book to survive dropped connections.
+    pub async fn maintained_order_book(&self, pair: types::CurrencyPairs) -> Result<book::MaintainedBook, Error> {
+        book::MaintainedBook::open(self.clone(), pair).await
     }
 
     /// Get a ticker
-    pub async fn get_ticker(&self, currency_pair: &str) -> Result<types::Ticker, Error> {
+    pub async fn get_ticker(&self, currency_pair: impl AsRef<str>) -> Result<types::Ticker, Error> {
+        let currency_pair = currency_pair.as_ref();
         let rest_method = format!("ticker/{}/", currency_pair);
         self.api_get(rest_method.as_str()).await
     }
 
+    /// Get tickers for all pairs
+    pub async fn get_all_tickers(&self) -> Result<Vec<types::PairTicker>, Error> {
+        let rest_method = "ticker/";
+        self.api_get(rest_method).await
+    }
+
+    /// GET any ticker endpoint (relative to `api/v2/`), accepting both the array reply of
+    /// `ticker/` and the single object of `ticker/{pair}/`
+    pub async fn get_tickers_raw(&self, rest_method: &str) -> Result<Vec<types::PairTicker>, Error> {
+        let tickers: types::Tickers = self.api_get(rest_method).await?;
+        Ok(tickers.0)
+    }
+
     /// Get a hourly ticker
-    pub async fn get_hourly_ticker(&self, currency_pair: &str) -> Result<types::Ticker, Error> {
+    pub async fn get_hourly_ticker(&self, currency_pair: impl AsRef<str>) -> Result<types::Ticker, Error> {
+        let currency_pair = currency_pair.as_ref();
         let rest_method = format!("ticker_hour/{}/", currency_pair);
         self.api_get(rest_method.as_str()).await
     }
 
     /// Get an order book
-    pub async fn get_order_book(&self, currency_pair: &str, group: Option<&str>) -> Result<types::OrderBook, Error> {
+    pub async fn get_order_book(&self, currency_pair: impl AsRef<str>, group: Option<&str>) -> Result<types::OrderBook, Error> {
+        self.get_order_book_with_options(currency_pair, group, RequestOptions::default()).await
+    }
+
+    pub async fn get_order_book_with_options(&self, currency_pair: impl AsRef<str>, group: Option<&str>, options: RequestOptions) -> Result<types::OrderBook, Error> {
+        let currency_pair = currency_pair.as_ref();
         let rest_method = format!("order_book/{}/{}", currency_pair, match group {
             None => { "".to_string() }
             Some(g) => { format!("?group={}", g) }
         });
-        self.api_get(rest_method.as_str()).await
+        self.cached_get(rest_method.as_str(), &options).await
+    }
+
+    /// Get the order book only if it's newer than `since_micro` (a previous book's
+    /// `microtimestamp`), `None` otherwise.
+    ///
+    /// Bitstamp has no conditional requests, the book is still downloaded but the
+    /// levels are only parsed when it changed.
+    pub async fn get_order_book_if_changed(&self, currency_pair: impl AsRef<str>, since_micro: u64) -> Result<Option<types::OrderBook>, Error> {
+        let currency_pair = currency_pair.as_ref();
+        #[derive(serde::Deserialize)]
+        struct Stamp {
+            microtimestamp: String,
+        }
+
+        let rest_method = format!("order_book/{}/", currency_pair);
+        let body: Option<&String> = None;
+        let (reply, _) = self.call_with_retries(ApiVersion::V2, "GET", rest_method.as_str(), body, true, &RequestOptions::default()).await;
+        let reply = reply?;
+        let stamp: Stamp = parse_reply(rest_method.as_str(), &reply)?;
+        if book::parse_microtimestamp(&stamp.microtimestamp)? <= since_micro {
+            return Ok(None);
+        }
+        parse_reply(rest_method.as_str(), &reply).map(Some)
     }
 
     /// Get a transaction list
-    pub async fn get_transactions(&self, currency_pair: &str, time: Option<Time>) -> Result<Vec<types::Transaction>, Error> {
+    pub async fn get_transactions(&self, currency_pair: impl AsRef<str>, time: Option<Time>) -> Result<Vec<types::Transaction>, Error> {
+        let currency_pair = currency_pair.as_ref();
         let rest_method = format!("transactions/{}/{}", currency_pair, match time {
             None => { "".to_string() }
-            Some(t) => { format!("?time={}", t.to_string().to_lowercase()) }
+            Some(t) => { format!("?time={}", t.as_str()) }
         });
-        self.api_get(rest_method.as_str()).await
+        self.cached_get(rest_method.as_str(), &RequestOptions::default()).await
     }
 
     /// Get a trading pair info
     pub async fn get_trading_pairs_info(&self) -> Result<Vec<types::PairInfo>, Error> {
-        let rest_method = format!("trading-pairs-info/");
-        self.api_get(rest_method.as_str()).await
+        let rest_method = "trading-pairs-info/";
+        self.api_get(rest_method).await
+    }
+
+    /// Get every pair's metadata together with its current ticker, both fetched concurrently
+    pub async fn market_overview(&self) -> Result<Vec<types::MarketOverview>, Error> {
+        let (pairs, tickers) = futures::try_join!(self.get_trading_pairs_info(), self.get_all_tickers())?;
+        let tickers: HashMap<String, types::Ticker> = tickers.into_iter().map(|t| (t.url_symbol(), t.ticker)).collect();
+        Ok(pairs.into_iter().map(|pair| {
+            let ticker = tickers.get(&pair.url_symbol);
+            types::MarketOverview {
+                enabled: pair.trading.eq_ignore_ascii_case("enabled"),
+                last: ticker.map(|t| t.last.clone()),
+                volume: ticker.map(|t| t.volume.clone()),
+                url_symbol: pair.url_symbol,
+                name: pair.name,
+                base_decimals: pair.base_decimals,
+                counter_decimals: pair.counter_decimals,
+                minimum_order: pair.minimum_order,
+            }
+        }).collect())
+    }
+
+    /// Get info for a single trading pair, loaded once from `get_pairs_info_map` and cached
+    pub async fn get_pair_info(&self, currency_pair: impl AsRef<str>) -> Result<types::PairInfo, Error> {
+        let currency_pair = currency_pair.as_ref();
+        if let Some(info) = self.pairs_info.lock().unwrap().get(currency_pair) {
+            return Ok(info.clone());
+        }
+        let pairs = self.get_pairs_info_map().await?;
+        pairs.get(currency_pair).cloned().ok_or_else(|| text_error(format!("Unknown currency pair: {}", currency_pair)))
+    }
+
+    /// Get every trading pair's info keyed on `url_symbol`, also refreshing what
+    /// `get_pair_info` returns. Goes through the `public_cache_ttl` cache when that's set,
+    /// otherwise keep the map around rather than calling this per order.
+    pub async fn get_pairs_info_map(&self) -> Result<HashMap<String, types::PairInfo>, Error> {
+        let pairs: Vec<types::PairInfo> = self.cached_get("trading-pairs-info/", &RequestOptions::default()).await?;
+        let pairs: HashMap<String, types::PairInfo> = pairs.into_iter().map(|pair| (pair.url_symbol.clone(), pair)).collect();
+        *self.pairs_info.lock().unwrap() = pairs.clone();
+        Ok(pairs)
     }
 
     /// Get EUR/USD conversion rate
     pub async fn get_eur_usd(&self) -> Result<types::ConversionRate, Error> {
-        let rest_method = format!("eur_usd/");
-        self.api_get(rest_method.as_str()).await
+        let rest_method = "eur_usd/";
+        self.api_get(rest_method).await
     }
 
     /// Get account ballance
     pub async fn get_balance(&self) -> Result<types::AccountBalance, Error> {
-        let rest_method = format!("balance/");
-        self.api_post(rest_method.as_str(), types::Offset { offset: "1".to_string() }).await
+        let rest_method = "balance/";
+        self.api_post(rest_method, types::Offset { offset: "1".to_string() }).await
+    }
+
+    /// Get the account's open orders across all pairs
+    pub async fn open_orders(&self) -> Result<Vec<types::OpenOrder>, Error> {
+        self.api_post("open_orders/all/", types::Offset { offset: "1".to_string() }).await
+    }
+
+    /// Balances and open orders as close to the same moment as possible, e.g. for
+    /// reconciliation. Both are requested concurrently, which keeps the window for changes
+    /// between them small, but two requests can't be truly atomic.
+    pub async fn account_snapshot(&self) -> Result<types::AccountSnapshot, Error> {
+        let captured_at = chrono::Utc::now();
+        let (balance, open_orders) = futures::future::try_join(self.get_balance(), self.open_orders()).await?;
+        Ok(types::AccountSnapshot { balance, open_orders, captured_at })
+    }
+
+    /// Get the balance of just the listed currencies (e.g. `["btc", "usd"]`, all of them when
+    /// empty), keyed on the lowercase currency code. Bitstamp always sends every currency,
+    /// the reply is filtered here. Currencies the reply doesn't include are left out.
+    pub async fn get_balance_filtered(&self, currencies: &[&str]) -> Result<HashMap<String, types::CurrencyBalance>, Error> {
+        let reply: HashMap<String, serde_json::Value> = self.api_post("balance/", types::Offset { offset: "1".to_string() }).await?;
+        let wanted: Vec<String> = if currencies.is_empty() {
+            reply.keys().filter_map(|key| key.strip_suffix("_balance")).map(str::to_string).collect()
+        } else {
+            currencies.iter().map(|currency| currency.to_lowercase()).collect()
+        };
+        Ok(wanted
+            .into_iter()
+            .filter_map(|currency| types::CurrencyBalance::from_reply(&reply, &currency).map(|balance| (currency, balance)))
+            .collect())
+    }
+
+    /// A WebSocket token, reused until it's close to expiring and then refreshed. Shared by
+    /// all clones of the client, concurrent callers wait for a single refresh. A failed
+    /// refresh isn't shared, the next waiter fetches again (like the public cache).
+    pub async fn websocket_token_cached(&self) -> Result<String, Error> {
+        let mut cached = self.websocket_token.lock().await;
+        if let Some((issued, token)) = cached.as_ref() {
+            let valid = Duration::from_secs(token.valid_sec.max(0) as u64);
+            // Leave time to subscribe with it
+            let margin = Duration::from_secs(30).min(valid / 2);
+            if issued.elapsed() + margin < valid {
+                return Ok(token.token.clone());
+            }
+        }
+        let issued = Instant::now();
+        let token = self.get_websocket_token().await?;
+        let value = token.token.clone();
+        *cached = Some((issued, token));
+        Ok(value)
+    }
+
+    /// Check the key and secret with a signed balance request, to fail at startup rather than
+    /// on the first order. Rejected credentials give an error for which
+    /// `Error::is_unauthorized` is true, other failures are returned as they are.
+    pub async fn verify_credentials(&self) -> Result<(), Error> {
+        let rest_method = "balance/";
+        match self.api_post::<serde_json::Value, _>(rest_method, types::Offset { offset: "1".to_string() }).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(match auth_error_message(&e) {
+                Some(message) => unauthorized(message),
+                None => e,
+            }),
+        }
+    }
+
+    /// Get a token for subscribing to private WebSocket channels with `BitstampEventStream::subscribe_private`
+    pub async fn get_websocket_token(&self) -> Result<types::WebsocketToken, Error> {
+        let rest_method = "websockets_token/";
+        self.api_post(rest_method, types::Offset { offset: "1".to_string() }).await
+    }
+
+    /// Place a limit order
+    ///
+    /// With a `client_order_id` set the order is safe to resend, so it's retried like a GET
+    /// when retries are enabled. A retry rejected because the id already exists means an
+    /// earlier attempt went through, that order is looked up and returned instead.
+    pub async fn limit_order(&self, side: types::TradeSide, currency_pair: impl AsRef<str>, request: types::LimitOrderRequest) -> Result<types::Order, Error> {
+        self.limit_order_with_options(side, currency_pair, request, RequestOptions::default()).await
+    }
+
+    pub async fn limit_order_with_options(&self, side: types::TradeSide, currency_pair: impl AsRef<str>, request: types::LimitOrderRequest, options: RequestOptions) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        if let types::TimeInForce::GoodTillDate(expire_time) = request.time_in_force {
+            if expire_time <= chrono::Utc::now().timestamp() {
+                return Err(text_error(format!("expire_time {} is in the past", expire_time)));
+            }
+        }
+        if request.post_only && matches!(request.time_in_force, types::TimeInForce::ImmediateOrCancel | types::TimeInForce::FillOrKill) {
+            return Err(text_error(format!("a post-only order can't be {:?}", request.time_in_force)));
+        }
+        let rest_method = format!("{}/{}/", side, currency_pair);
+        let client_order_id = request.client_order_id.clone();
+        let post_only = request.post_only;
+        match self.place_order(rest_method.as_str(), request, client_order_id, &options).await {
+            Err(e) if post_only => Err(post_only_rejection(&e).map(post_only_rejected).unwrap_or(e)),
+            result => result,
+        }
+    }
+
+    /// Place a market order, retried the same way as `limit_order` when a `client_order_id` is set
+    pub async fn market_order(&self, side: types::TradeSide, currency_pair: impl AsRef<str>, request: types::MarketOrderRequest) -> Result<types::Order, Error> {
+        self.market_order_with_options(side, currency_pair, request, RequestOptions::default()).await
+    }
+
+    pub async fn market_order_with_options(&self, side: types::TradeSide, currency_pair: impl AsRef<str>, request: types::MarketOrderRequest, options: RequestOptions) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        let rest_method = format!("{}/market/{}/", side, currency_pair);
+        let client_order_id = request.client_order_id.clone();
+        self.place_order(rest_method.as_str(), request, client_order_id, &options).await
+    }
+
+    /// Place a batch of orders, at most `MAX_CONCURRENT_ORDERS` in flight at a time, and
+    /// return their results in the order of `orders`.
+    ///
+    /// The orders reach Bitstamp in no particular order and one failing doesn't stop the
+    /// others. Give each a `client_order_id` to tell afterwards which ones were placed when a
+    /// result is an error such as a timeout.
+    pub async fn place_orders(&self, orders: Vec<types::OrderRequest>) -> Vec<Result<types::Order, Error>> {
+        futures::stream::iter(orders)
+            .map(|order| async move {
+                match order {
+                    types::OrderRequest::Limit { side, currency_pair, request } => self.limit_order(side, &currency_pair, request).await,
+                    types::OrderRequest::Market { side, currency_pair, request } => self.market_order(side, &currency_pair, request).await,
+                }
+            })
+            .buffered(MAX_CONCURRENT_ORDERS)
+            .collect()
+            .await
+    }
+
+    /// Place a limit buy order
+    pub async fn buy_limit_order(&self, currency_pair: impl AsRef<str>, amount: &str, price: &str) -> Result<types::Order, Error> {
+        self.limit_order(types::TradeSide::Buy, currency_pair, types::LimitOrderRequest { amount: amount.to_string(), price: price.to_string(), ..Default::default() }).await
+    }
+
+    /// Place a limit sell order
+    pub async fn sell_limit_order(&self, currency_pair: impl AsRef<str>, amount: &str, price: &str) -> Result<types::Order, Error> {
+        self.limit_order(types::TradeSide::Sell, currency_pair, types::LimitOrderRequest { amount: amount.to_string(), price: price.to_string(), ..Default::default() }).await
+    }
+
+    /// Place a market buy order
+    pub async fn buy_market_order(&self, currency_pair: impl AsRef<str>, amount: &str) -> Result<types::Order, Error> {
+        self.market_order(types::TradeSide::Buy, currency_pair, types::MarketOrderRequest { amount: amount.to_string(), ..Default::default() }).await
+    }
+
+    /// Place a market sell order
+    pub async fn sell_market_order(&self, currency_pair: impl AsRef<str>, amount: &str) -> Result<types::Order, Error> {
+        self.market_order(types::TradeSide::Sell, currency_pair, types::MarketOrderRequest { amount: amount.to_string(), ..Default::default() }).await
+    }
+
+    /// Place a limit buy order, amount and price are truncated to the pair's precision
+    pub async fn buy_limit_order_decimal(&self, currency_pair: impl AsRef<str>, amount: Decimal, price: Decimal) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        let (amount, price) = self.format_limit_order(currency_pair, amount, price).await?;
+        self.buy_limit_order(currency_pair, amount.as_str(), price.as_str()).await
+    }
+
+    /// Place a limit sell order, amount and price are truncated to the pair's precision
+    pub async fn sell_limit_order_decimal(&self, currency_pair: impl AsRef<str>, amount: Decimal, price: Decimal) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        let (amount, price) = self.format_limit_order(currency_pair, amount, price).await?;
+        self.sell_limit_order(currency_pair, amount.as_str(), price.as_str()).await
+    }
+
+    /// Place a market buy order, amount is truncated to the pair's precision
+    pub async fn buy_market_order_decimal(&self, currency_pair: impl AsRef<str>, amount: Decimal) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        let info = self.get_pair_info(currency_pair).await?;
+        self.buy_market_order(currency_pair, truncate_to(amount, info.base_decimals).as_str()).await
+    }
+
+    /// Place a market sell order, amount is truncated to the pair's precision
+    pub async fn sell_market_order_decimal(&self, currency_pair: impl AsRef<str>, amount: Decimal) -> Result<types::Order, Error> {
+        let currency_pair = currency_pair.as_ref();
+        let info = self.get_pair_info(currency_pair).await?;
+        self.sell_market_order(currency_pair, truncate_to(amount, info.base_decimals).as_str()).await
+    }
+
+    /// Get the account's transactions, for a single pair or across all of them
+    pub async fn user_transactions(&self, currency_pair: Option<&str>, request: types::UserTransactionsRequest) -> Result<Vec<types::UserTransaction>, Error> {
+        self.user_transactions_with_options(currency_pair, request, RequestOptions::default()).await
+    }
+
+    pub async fn user_transactions_with_options(&self, currency_pair: Option<&str>, request: types::UserTransactionsRequest, options: RequestOptions) -> Result<Vec<types::UserTransaction>, Error> {
+        let rest_method = match currency_pair {
+            None => "user_transactions/".to_string(),
+            Some(pair) => format!("user_transactions/{}/", pair),
+        };
+        self.api_post_with(rest_method.as_str(), request, &options).await
+    }
+
+    /// Get one of the account's transactions by id.
+    ///
+    /// Bitstamp has no lookup by id, so this is a `user_transactions` request from just
+    /// before `id` onwards, oldest first, filtered for it. That's one signed request, but
+    /// Bitstamp returns up to 1000 rows for a `since_id` query.
+    pub async fn user_transaction(&self, id: u64) -> Result<types::UserTransaction, Error> {
+        let request = types::UserTransactionsRequest {
+            // Whether `since_id` itself is included isn't documented, start one earlier
+            since_id: Some(id.saturating_sub(1).to_string()),
+            sort: Some(types::Sort::Asc),
+            ..Default::default()
+        };
+        let id = id.to_string();
+        self.user_transactions(None, request)
+            .await?
+            .into_iter()
+            .find(|transaction| transaction.id == id)
+            .ok_or_else(|| text_error(format!("no user transaction with id {}", id)))
+    }
+
+    /// Get the account's cryptocurrency deposits and withdrawals, newest first. Trades are in
+    /// `user_transactions` instead.
+    pub async fn crypto_transactions(&self, include_ious: bool, limit: u32, offset: u32) -> Result<types::CryptoTransactions, Error> {
+        let request = types::CryptoTransactionsRequest { limit, offset, include_ious };
+        self.api_post("crypto-transactions/", request).await
+    }
+
+    /// All cryptocurrency deposits and withdrawals, requested page by page as the stream is
+    /// read. Each page is sorted newest first with its transactions tagged by kind, see
+    /// `CryptoTransactions::into_tagged`.
+    pub fn crypto_transactions_stream(
+        &self,
+        include_ious: bool,
+    ) -> impl futures::Stream<Item = Result<(types::CryptoTransactionKind, types::CryptoTransaction), Error>> + '_ {
+        const PAGE: u32 = 1000;
+        futures::stream::try_unfold((0, VecDeque::new(), false), move |(mut offset, mut buffer, mut done)| async move {
+            loop {
+                if let Some(transaction) = buffer.pop_front() {
+                    return Ok(Some((transaction, (offset, buffer, done))));
+                }
+                if done {
+                    return Ok(None);
+                }
+                let page = self.crypto_transactions(include_ious, PAGE, offset).await?.into_tagged();
+                // Don't rely on whether the limit is per list or in total, a short page is the last
+                done = page.len() < PAGE as usize;
+                offset += PAGE;
+                buffer.extend(page);
+            }
+        })
+    }
+
+    /// Get the deposit address for a currency (e.g. `"btc"`), for XRP, XLM and HBAR together
+    /// with the destination tag or memo that has to accompany deposits
+    pub async fn deposit_address(&self, currency: &str) -> Result<types::DepositAddress, Error> {
+        let rest_method = format!("{}_address/", currency.to_lowercase());
+        let address: types::DepositAddress = self.api_post(rest_method.as_str(), types::Offset { offset: "1".to_string() }).await?;
+        if types::requires_destination_tag(currency) && address.destination_tag.as_deref().unwrap_or_default().is_empty() {
+            return Err(text_error(format!("{} deposit address came without a destination tag", currency)));
+        }
+        Ok(address)
+    }
+
+    /// Withdraw a currency (e.g. `"btc"`) to an external address.
+    ///
+    /// Fails without sending anything when the currency needs a destination tag (see
+    /// `types::requires_destination_tag`) and `request.destination_tag` is unset.
+    pub async fn withdrawal(&self, currency: &str, request: types::WithdrawalRequest) -> Result<types::WithdrawalResult, Error> {
+        #[derive(Serialize)]
+        struct Withdrawal {
+            amount: String,
+            address: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            destination_tag: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            memo_id: Option<String>,
+        }
+
+        let currency = currency.to_lowercase();
+        let tag = request.destination_tag.filter(|tag| !tag.is_empty());
+        if types::requires_destination_tag(&currency) && tag.is_none() {
+            return Err(text_error(format!("{} withdrawals need a destination tag", currency)));
+        }
+        // XRP calls it a destination tag, the others a memo
+        let (destination_tag, memo_id) = match currency.as_str() {
+            "xlm" | "hbar" => (None, tag),
+            _ => (tag, None),
+        };
+        let rest_method = format!("{}_withdrawal/", currency);
+        self.api_post(rest_method.as_str(), Withdrawal { amount: request.amount, address: request.address, destination_tag, memo_id }).await
+    }
+
+    /// Cancel an order
+    pub async fn cancel_order(&self, id: &str) -> Result<types::CancelResult, Error> {
+        let rest_method = "cancel_order/";
+        self.api_post(rest_method, types::CancelOrderRequest { id: id.to_string() }).await
+    }
+
+    /// Cancel an order by the `client_order_id` it was placed with.
+    ///
+    /// `cancel_order/` only takes exchange ids, so this first resolves the id with
+    /// `order_status_by_client_id` and then cancels, costing two signed requests.
+    pub async fn cancel_order_by_client_id(&self, client_order_id: &str) -> Result<types::CancelResult, Error> {
+        let status = self.order_status_by_client_id(client_order_id).await?;
+        self.cancel_order(status.id.as_str()).await
+    }
+
+    /// Get the status of an order
+    pub async fn order_status(&self, id: &str) -> Result<types::OrderStatus, Error> {
+        let rest_method = "order_status/";
+        self.api_post(rest_method, types::OrderStatusRequest { id: Some(id.to_string()), client_order_id: None }).await
+    }
+
+    /// Get the status of an order by the `client_order_id` it was placed with
+    pub async fn order_status_by_client_id(&self, client_order_id: &str) -> Result<types::OrderStatus, Error> {
+        let rest_method = "order_status/";
+        self.api_post(rest_method, types::OrderStatusRequest { id: None, client_order_id: Some(client_order_id.to_string()) }).await
+    }
+
+    /// Lower-level GET of any REST path (relative to `api/v2/`, e.g. `"ticker/btcusd/"`),
+    /// deserialized into a caller-provided type. Prefer the typed methods where they exist.
+    pub async fn raw_get<T: DeserializeOwned>(&self, rest_method: &str) -> Result<T, Error> {
+        self.api_get(rest_method).await
+    }
+
+    /// Lower-level signed POST of any REST path (relative to `api/v2/`) with a custom body,
+    /// deserialized into a caller-provided type. Prefer the typed methods where they exist.
+    pub async fn raw_post<T: DeserializeOwned, U: Serialize>(&self, rest_method: &str, body: U) -> Result<T, Error> {
+        self.api_post(rest_method, body).await
+    }
+
+    /// `raw_get` of an endpoint of another API version, e.g. one only Bitstamp's v1 API has
+    pub async fn raw_get_version<T: DeserializeOwned>(&self, version: ApiVersion, rest_method: &str) -> Result<T, Error> {
+        let body: Option<String> = None;
+        self.rest_api(version, "GET", rest_method, body, true, &RequestOptions::default()).await
+    }
+
+    /// `raw_post` to an endpoint of another API version
+    pub async fn raw_post_version<T: DeserializeOwned, U: Serialize>(&self, version: ApiVersion, rest_method: &str, body: U) -> Result<T, Error> {
+        self.rest_api(version, "POST", rest_method, Some(body), false, &RequestOptions::default()).await
     }
 
     // PRIVATE
 
+    /// Cheap public request to get a pooled connection open
+    async fn warm_up(&self) {
+        let body: Option<&String> = None;
+        self.pace().await;
+        match self.call_web_api_raw(ApiVersion::V2, "GET", "eur_usd/", body).await {
+            Ok(_) => debug!("REST connection warmed up"),
+            Err(e) => debug!("REST warm up failed: {}", e),
+        }
+    }
+
+    async fn format_limit_order(&self, currency_pair: &str, amount: Decimal, price: Decimal) -> Result<(String, String), Error> {
+        let info = self.get_pair_info(currency_pair).await?;
+        Ok((truncate_to(amount, info.base_decimals), truncate_to(price, info.counter_decimals)))
+    }
+
+    async fn place_order<U: Serialize>(
+        &self,
+        rest_method: &str,
+        body: U,
+        client_order_id: Option<String>,
+        options: &RequestOptions,
+    ) -> Result<types::Order, Error> {
+        let client_order_id = match client_order_id {
+            None => return self.api_post_with(rest_method, body, options).await,
+            Some(id) => id,
+        };
+        let (reply, retries) = self.call_with_retries(ApiVersion::V2, "POST", rest_method, Some(&body), true, options).await;
+        match reply {
+            Err(e) if retries > 0 && e.code() == Some(BitstampErrorCode::DuplicateClientOrderId) => {
+                debug!("Order {} was already placed by an earlier attempt", client_order_id);
+                self.placed_order(&client_order_id).await
+            }
+            reply => parse_reply(rest_method, &reply?),
+        }
+    }
+
+    /// The order placed as `client_order_id`, as Bitstamp has it: the open order while it
+    /// rests on the book, otherwise built from its status, see `OrderStatus::filled_amount`
+    async fn placed_order(&self, client_order_id: &str) -> Result<types::Order, Error> {
+        let open = self.open_orders().await?;
+        if let Some(order) = open.into_iter().find(|order| order.client_order_id.as_deref() == Some(client_order_id)) {
+            return Ok(types::Order {
+                id: order.id,
+                datetime: order.datetime,
+                type_field: order.type_field,
+                price: order.price,
+                amount: order.amount,
+                client_order_id: order.client_order_id,
+            });
+        }
+        self.order_status_by_client_id(client_order_id).await?.placed_order()
+    }
+
     async fn api_get<T: DeserializeOwned>(&self, rest_method: &str) -> Result<T, Error> {
+        self.api_get_with(rest_method, &RequestOptions::default()).await
+    }
+
+    /// GET through the public cache if it's enabled
+    async fn cached_get<T: DeserializeOwned>(&self, rest_method: &str, options: &RequestOptions) -> Result<T, Error> {
+        let cache = match &self.public_cache {
+            None => return self.api_get_with(rest_method, options).await,
+            Some(cache) => cache,
+        };
+        let body: Option<&String> = None;
+        let reply = cache
+            .get_or_fetch(rest_method, || async { self.call_with_retries(ApiVersion::V2, "GET", rest_method, body, true, options).await.0 })
+            .await?;
+        parse_reply(rest_method, &reply)
+    }
+
+    async fn api_get_with<T: DeserializeOwned>(&self, rest_method: &str, options: &RequestOptions) -> Result<T, Error> {
         let body: Option<String> = None;
-        self.rest_api("GET", rest_method, body).await
+        self.rest_api(ApiVersion::V2, "GET", rest_method, body, true, options).await
     }
 
     async fn api_post<T: DeserializeOwned, U: Serialize>(
@@ -215,54 +963,107 @@ impl Bitstamp {
         rest_method: &str,
         body: U,
     ) -> Result<T, Error> {
-        self.rest_api("POST", rest_method, Some(body)).await
+        self.api_post_with(rest_method, body, &RequestOptions::default()).await
+    }
+
+    async fn api_post_with<T: DeserializeOwned, U: Serialize>(
+        &self,
+        rest_method: &str,
+        body: U,
+        options: &RequestOptions,
+    ) -> Result<T, Error> {
+        self.rest_api(ApiVersion::V2, "POST", rest_method, Some(body), false, options).await
     }
 
     async fn rest_api<T: DeserializeOwned, U: Serialize>(
         &self,
+        version: ApiVersion,
         http_method: &str,
         rest_method: &str,
         body: Option<U>,
+        retry: bool,
+        options: &RequestOptions,
     ) -> Result<T, Error> {
-        match self.call_web_api_raw(http_method, rest_method, body).await {
-            Ok(reply) => {
-                let de: Result<T, _> = serde_json::from_str(reply.as_str());
-                match de {
-                    Ok(reply) => Ok(reply),
-                    Err(e) => {
-                        debug!("Couldn't parse reply for {} call: {}", rest_method, e);
-                        debug!("Source JSON: {}", reply);
-                        Err(text_error_with_inner(format!("failed to parse reply: {}", e), e))
-                    }
+        let (reply, _) = self.call_with_retries(version, http_method, rest_method, body.as_ref(), retry, options).await;
+        parse_reply(rest_method, &reply?)
+    }
+
+    /// Send a request, resending it on retryable failures when `retry` is set.
+    /// Returns the last result along with the number of retries made, not counting a resend
+    /// after a rejected nonce (which can't have executed).
+    async fn call_with_retries<U: Serialize>(
+        &self,
+        version: ApiVersion,
+        http_method: &str,
+        rest_method: &str,
+        body: Option<&U>,
+        retry: bool,
+        options: &RequestOptions,
+    ) -> (Result<String, Error>, u32) {
+        let max_retries = options.max_retries.unwrap_or(self.max_retries);
+        let request_timeout = options.timeout.or(self.request_timeout);
+        let mut retries = 0;
+        // The nonce resend isn't a retry, it doesn't count against `max_retries`
+        let mut nonce_resent = false;
+        loop {
+            // Before the timeout starts, waiting for a slot isn't the request being slow
+            self.pace().await;
+            let reply = match request_timeout {
+                None => self.call_web_api_raw(version, http_method, rest_method, body).await,
+                Some(t) => tokio::time::timeout(t, self.call_web_api_raw(version, http_method, rest_method, body))
+                    .await
+                    .unwrap_or_else(|_| Err(timeout(t))),
+            };
+            match reply {
+                // Rejected before executing, so safe to resend even for POSTs. The next
+                // attempt signs with a fresh nonce.
+                Err(e) if !nonce_resent && e.code() == Some(BitstampErrorCode::InvalidNonce) => {
+                    debug!("{} {} nonce rejected, resending with a new one", http_method, rest_method);
+                    nonce_resent = true;
                 }
+                Err(e) if retry && retries < max_retries && e.is_retryable() => {
+                    let delay = self.retry_backoff * 2u32.saturating_pow(retries);
+                    debug!("{} {} failed ({}), retrying in {:?}", http_method, rest_method, e, delay);
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                reply => return (reply, retries),
             }
-            Err(e) => Err(e),
+        }
+    }
+
+    async fn pace(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
         }
     }
 
     async fn call_web_api_raw<T: Serialize>(
         &self,
+        version: ApiVersion,
         http_method: &str,
         rest_method: &str,
-        body: Option<T>,
+        body: Option<&T>,
     ) -> Result<String, Error> {
-        let prefix = String::from(REST_HOST_PREFIX);
-        let url = format!("{}/{}", prefix, rest_method);
-
+        let url = format!("{}/{}/{}", REST_HOST, version.path_prefix(), rest_method);
 
         debug!("Calling {} {:?}", http_method, url);
-        let mut builder = Request::builder().method(http_method).uri(format!("https://{}", url));
+        let mut builder = Request::builder()
+            .method(http_method)
+            .uri(format!("https://{}", url))
+            .header("Accept", "application/json")
+            .header("Accept-Encoding", "identity");
         let body = if http_method.eq("POST") {
             let auth = format!("BITSTAMP {}", self.key);
-            let nonce = Uuid::new_v4().to_string();
-            let mut content_type = "application/x-www-form-urlencoded";
-            let now = chrono::Utc::now();
-            let timestamp = format!("{}{}", now.timestamp(), now.nanosecond() / 1000000); // TODO
-            let mut payload = match body {
-                Some(obj) => serde_json::to_string(&obj).unwrap(),
+            let nonce = self.nonce_generator.try_next().map_err(|e| text_error_with_inner(format!("no nonce for {}: {}", rest_method, e), e))?;
+            let content_type = "application/x-www-form-urlencoded";
+            // Milliseconds since the epoch, always 13 digits
+            let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+            let payload = match body {
+                Some(obj) => form_body(obj)?,
                 None => "".to_string(),
             };
-            let message = format!("{}POST{}{}{}{}v2{}", auth, url, content_type, nonce, timestamp, payload);
+            let message = format!("{}POST{}{}{}{}{}{}", auth, url, content_type, nonce, timestamp, version.auth_version(), payload);
             debug!("{}", message);
             let mut mac = HmacSha256::new_from_slice(self.secret.as_bytes()).expect("Failed to create hmac");
             mac.update(message.as_bytes());
@@ -272,7 +1073,7 @@ impl Bitstamp {
             builder = builder.header("X-Auth-Signature", signature);
             builder = builder.header("X-Auth-Nonce", nonce);
             builder = builder.header("X-Auth-Timestamp", timestamp);
-            builder = builder.header("X-Auth-Version", "v2");
+            builder = builder.header("X-Auth-Version", version.auth_version());
             builder = builder.header("Content-Type", content_type);
             Body::from(payload)
         } else {
@@ -283,35 +1084,140 @@ impl Bitstamp {
 
         let req = builder.body(body).unwrap();
 
-        match self.client.request(req).await {
+        match self.transport.request(req).await {
             Ok(mut resp) => {
-                let mut reply = String::new();
+                // We ask for identity, but a proxy or CDN may compress anyway. There's no
+                // decoder in the crate, so fail clearly instead of parsing compressed bytes.
+                if let Some(encoding) = resp.headers().get("Content-Encoding") {
+                    let encoding = encoding.to_str().unwrap_or_default().trim().to_lowercase();
+                    if !encoding.is_empty() && encoding != "identity" {
+                        return Err(text_error(format!("unsupported Content-Encoding {:?} in reply", encoding)));
+                    }
+                }
+                let mut bytes = Vec::new();
                 while let Some(chunk) = resp.body_mut().data().await {
-                    use std::str;
-
-                    let chunk = chunk.unwrap();
-                    let strchunk = str::from_utf8(&chunk).unwrap();
-                    reply.push_str(&strchunk);
+                    let chunk = chunk.map_err(|e| transport_error(format!("failed to read reply: {}", e), e))?;
+                    if bytes.len() + chunk.len() > self.max_response_bytes {
+                        return Err(response_too_large(self.max_response_bytes));
+                    }
+                    bytes.extend_from_slice(&chunk);
                 }
-                if !resp.status().is_success() {
-                    match serde_json::from_str::<types::V2Error>(&reply) {
-                        Ok(status) => {
-                            debug!("Request failed with {:#?}", status);
-                            return Err(v2_error(resp.status(), status.reason, status.code));
-                        }
-                        Err(_) => {
-                            match serde_json::from_str::<types::V1Error>(&reply) {
-                                Ok(status) => {
-                                    return Err(v1_error(resp.status(), status.error));
-                                }
-                                Err(_) => { return Err(status_code(resp.status())); }
-                            }
-                        }
-                    };
+                // Decoded once at the end, a character may be split across chunks
+                let reply = String::from_utf8(bytes).map_err(|e| text_error_with_inner(format!("reply is not UTF-8: {}", e), e))?;
+                if let Some(e) = reply_error(resp.status(), &reply) {
+                    debug!("Request failed with {}: {}", resp.status(), reply);
+                    return Err(e);
                 }
                 Ok(reply)
             }
-            Err(e) => Err(text_error_with_inner(format!("request failed: {}", e), e)),
+            Err(e) => Err(transport_error(format!("request failed: {}", e), e)),
+        }
+    }
+}
+
+fn parse_reply<T: DeserializeOwned>(rest_method: &str, reply: &str) -> Result<T, Error> {
+    parse_body(reply).inspect_err(|_| debug!("Couldn't parse reply for {} call", rest_method))
+}
+
+/// Decode a reply body that isn't an error
+fn parse_body<T: DeserializeOwned>(reply: &str) -> Result<T, Error> {
+    let de: Result<T, _> = json::from_str(reply);
+    match de {
+        Ok(reply) => Ok(reply),
+        Err(e) => {
+            debug!("Couldn't parse reply: {}", e);
+            debug!("Source JSON: {}", reply);
+            Err(text_error_with_inner(format!("failed to parse reply: {}", e), e))
+        }
+    }
+}
+
+/// Readable explanation for errors caused by the credentials
+fn auth_error_message(e: &Error) -> Option<String> {
+    match e.kind() {
+        Kind::ErrorV2(_, reason, _) => match e.code()? {
+            BitstampErrorCode::InvalidApiKey => Some(format!("API key not found, check the key ({})", reason)),
+            BitstampErrorCode::NoPermission => Some(format!("API key lacks permission for this call ({})", reason)),
+            BitstampErrorCode::InvalidNonce => Some(format!("nonce rejected, was it reused by another client with the same key? ({})", reason)),
+            BitstampErrorCode::InvalidSignature => Some(format!("signature rejected, check the secret ({})", reason)),
+            BitstampErrorCode::MakerOrCancelRejected | BitstampErrorCode::DuplicateClientOrderId | BitstampErrorCode::Other(_) => None,
+        },
+        _ => match e.status() {
+            Some(StatusCode::UNAUTHORIZED) | Some(StatusCode::FORBIDDEN) => Some(e.to_string()),
+            _ => None,
+        },
+    }
+}
+
+/// The reason of a maker-or-cancel order being refused for matching immediately
+fn post_only_rejection(e: &Error) -> Option<String> {
+    match e.kind() {
+        Kind::ErrorV2(_, reason, _) if e.code() == Some(BitstampErrorCode::MakerOrCancelRejected) => Some(reason.clone()),
+        _ => None,
+    }
+}
+
+/// Truncate (never round up) so an order can't ask for more than the available balance
+fn truncate_to(value: Decimal, decimals: i64) -> String {
+    value.trunc(decimals.max(0) as u32).to_string()
+}
+
+/// Bitstamp reads POST parameters form encoded, the same string is sent and signed. Bodies go
+/// through JSON first so request types only need `Serialize`, booleans become `True`/`False`
+/// as in Bitstamp's documentation and `null`s are left out.
+fn form_body<T: Serialize>(body: &T) -> Result<String, Error> {
+    let fields = match serde_json::to_value(body) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        Ok(serde_json::Value::Null) => return Ok("".to_string()),
+        Ok(other) => return Err(text_error(format!("request body must be an object, got {}", other))),
+        Err(e) => return Err(text_error_with_inner(format!("failed to encode request body: {}", e), e)),
+    };
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    for (name, value) in fields {
+        match value {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(value) => {
+                form.append_pair(&name, &value);
+            }
+            serde_json::Value::Number(value) => {
+                form.append_pair(&name, &value.to_string());
+            }
+            serde_json::Value::Bool(value) => {
+                form.append_pair(&name, if value { "True" } else { "False" });
+            }
+            other => return Err(text_error(format!("can't form encode {} = {}", name, other))),
         }
     }
-}
\ No newline at end of file
+    Ok(form.finish())
+}
+
+/// The error a reply stands for, if any: a V2 error body whatever the status, otherwise any
+/// non-2xx status (with the message of a V1 error body if there is one)
+fn reply_error(status: StatusCode, reply: &str) -> Option<Error> {
+    if let Some(e) = reply_status_error(Some(status), reply) {
+        return Some(e);
+    }
+    if status.is_success() {
+        return None;
+    }
+    Some(match json::from_str::<types::V1Error>(reply) {
+        Ok(error) => v1_error(status, error.error),
+        Err(_) => status_code(status),
+    })
+}
+
+/// V2 error body, Bitstamp sometimes sends it with a 2xx status code and `reason` may be
+/// a string or an object of per-field messages
+fn reply_status_error(status: Option<StatusCode>, reply: &str) -> Option<Error> {
+    let value: serde_json::Value = json::from_str(reply).ok()?;
+    if value.get("status")?.as_str()? != "error" {
+        return None;
+    }
+    let reason = match value.get("reason") {
+        Some(serde_json::Value::String(reason)) => reason.clone(),
+        Some(reason) => reason.to_string(),
+        None => "".to_string(),
+    };
+    let code = value.get("code").and_then(|c| c.as_str()).unwrap_or_default().to_string();
+    Some(v2_error(status, reason, code))
+}
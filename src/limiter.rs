@@ -0,0 +1,36 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Paces requests to a steady rate, allowing a burst of up to one second's worth.
+///
+/// Callers over the budget are delayed, never rejected. No refill task is needed, every
+/// caller reserves its own slot (GCRA, the virtual scheduling form of a token bucket).
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    burst: Duration,
+    /// When the bucket would be empty again if nobody else came
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(requests_per_second: u32) -> RateLimiter {
+        let requests_per_second = requests_per_second.max(1);
+        let interval = Duration::from_secs(1) / requests_per_second;
+        RateLimiter { interval, burst: interval * (requests_per_second - 1), next: Mutex::new(Instant::now()) }
+    }
+
+    /// Wait for a slot
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next).max(now);
+            *next = start + self.interval;
+            start.checked_sub(self.burst).map_or(Duration::ZERO, |at| at.saturating_duration_since(now))
+        };
+        if !wait.is_zero() {
+            debug!("Rate limited, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Nonces a `MonotonicNonceGenerator` reserves with each save to its `NonceStore`
+const NONCE_RESERVATION: u64 = 10_000;
+
+/// Source of the `X-Auth-Nonce` value sent with every signed request.
+///
+/// One generator is shared by all clones of a client and called concurrently from every
+/// in-flight signed request, so `next` must never hand out the same value twice.
+pub trait NonceGenerator: Send + Sync {
+    /// Must not panic, a generator that can fail should also override `try_next`
+    fn next(&self) -> String;
+
+    /// What signed requests call, a failure fails the request instead of sending it
+    fn try_next(&self) -> io::Result<String> {
+        Ok(self.next())
+    }
+}
+
+/// Random UUID v4 nonces, the default.
+#[derive(Debug, Default, Clone)]
+pub struct UuidNonceGenerator;
+
+impl NonceGenerator for UuidNonceGenerator {
+    fn next(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// Strictly increasing nonces, zero padded to the 36 characters Bitstamp expects.
+///
+/// The counter is seeded from the current time in microseconds, so nonces also keep
+/// increasing across restarts as long as the clock does. Use `with_store` to not depend on
+/// the clock.
+pub struct MonotonicNonceGenerator {
+    counter: AtomicU64,
+    store: Option<Reservation>,
+}
+
+/// Nonces up to `reserved` are saved to `store` as handed out, `saving` serializes saves
+struct Reservation {
+    store: Box<dyn NonceStore>,
+    reserved: AtomicU64,
+    saving: Mutex<()>,
+}
+
+impl MonotonicNonceGenerator {
+    pub fn new() -> Self {
+        MonotonicNonceGenerator::starting_at(chrono::Utc::now().timestamp_micros() as u64)
+    }
+
+    pub fn starting_at(start: u64) -> Self {
+        MonotonicNonceGenerator { counter: AtomicU64::new(start), store: None }
+    }
+
+    /// Resume above the high-water mark in `store`, or the current time if that's higher.
+    ///
+    /// Rather than saving every nonce, blocks of them are reserved ahead and only the end of
+    /// the block is saved, so a restart may skip up to a block of nonces but never reuses one.
+    /// A failed save fails `try_next`, and with it the signed request, and is retried with
+    /// the next nonce.
+    pub fn with_store<S: NonceStore + 'static>(store: S) -> io::Result<Self> {
+        let now = chrono::Utc::now().timestamp_micros() as u64;
+        let start = store.load()?.map_or(now, |mark| mark.max(now));
+        let reserved = start + NONCE_RESERVATION;
+        store.save(reserved)?;
+        Ok(MonotonicNonceGenerator {
+            counter: AtomicU64::new(start),
+            store: Some(Reservation { store: Box::new(store), reserved: AtomicU64::new(reserved), saving: Mutex::new(()) }),
+        })
+    }
+
+    /// The next nonce, along with whether it's covered by a saved reservation
+    fn take(&self) -> (u64, io::Result<()>) {
+        let nonce = self.counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let covered = self.store.as_ref().map_or(Ok(()), |store| store.cover(nonce));
+        (nonce, covered)
+    }
+}
+
+impl Reservation {
+    /// Make sure `nonce` is covered by a saved reservation before it's used
+    fn cover(&self, nonce: u64) -> io::Result<()> {
+        if nonce <= self.reserved.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let _saving = self.saving.lock().unwrap();
+        if nonce <= self.reserved.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let reserved = nonce + NONCE_RESERVATION;
+        if let Err(e) = self.store.save(reserved) {
+            error!("Failed to save nonce high-water mark {}: {}", reserved, e);
+            return Err(e);
+        }
+        self.reserved.store(reserved, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MonotonicNonceGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MonotonicNonceGenerator")
+            .field("counter", &self.counter)
+            .field("reserved", &self.store.as_ref().map(|store| store.reserved.load(Ordering::SeqCst)))
+            .finish()
+    }
+}
+
+impl Default for MonotonicNonceGenerator {
+    fn default() -> Self {
+        MonotonicNonceGenerator::new()
+    }
+}
+
+impl NonceGenerator for MonotonicNonceGenerator {
+    /// Hands the nonce out even when saving the reservation failed (the failure is logged),
+    /// it may then be reused after a restart. Signed requests use `try_next`, which fails instead.
+    fn next(&self) -> String {
+        let (nonce, _) = self.take();
+        format!("{:036}", nonce)
+    }
+
+    fn try_next(&self) -> io::Result<String> {
+        let (nonce, covered) = self.take();
+        covered?;
+        Ok(format!("{:036}", nonce))
+    }
+}
+
+/// Where `MonotonicNonceGenerator::with_store` keeps its high-water mark between restarts
+pub trait NonceStore: Send + Sync {
+    /// The last saved mark, `None` if none was saved yet
+    fn load(&self) -> io::Result<Option<u64>>;
+    /// Called before any nonce above the previous mark is handed out
+    fn save(&self, mark: u64) -> io::Result<()>;
+}
+
+/// Keeps the mark in a file, replaced atomically through a temporary file next to it
+#[derive(Debug, Clone)]
+pub struct FileNonceStore {
+    path: PathBuf,
+}
+
+impl FileNonceStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileNonceStore { path: path.into() }
+    }
+}
+
+impl NonceStore for FileNonceStore {
+    fn load(&self) -> io::Result<Option<u64>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(mark) => mark.trim().parse().map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, mark: u64) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, mark.to_string())?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
@@ -0,0 +1,815 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use hyper_tls::native_tls;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::{error::UrlError, handshake::client::Response as WsResponse, Error as WsError, Message};
+use tokio_tungstenite::{client_async_tls_with_config, connect_async_with_config, Connector, MaybeTlsStream, WebSocketStream};
+
+use crate::error::{self, text_error, text_error_with_inner, unauthorized, websocket_error, BitstampErrorCode, Error};
+use crate::json;
+use crate::types;
+
+pub type WStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+/// See `BitstampEventStream::set_raw_hook`
+pub type RawHook = Box<dyn Fn(&str) + Send>;
+
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Connection events kept for `BitstampEventStream::connection_history`
+const CONNECTION_HISTORY_LEN: usize = 32;
+
+/// How to (re)open the WebSocket connection
+#[derive(Clone)]
+pub(crate) struct StreamConfig {
+    pub(crate) url: String,
+    pub(crate) tls_connector: Option<native_tls::TlsConnector>,
+    pub(crate) timeout: Duration,
+    pub(crate) write_timeout: Duration,
+    pub(crate) auto_reconnect: bool,
+    pub(crate) reconnect_delay: Duration,
+    pub(crate) max_reconnect_attempts: Option<u32>,
+    /// Message and frame size limits
+    pub(crate) ws_config: WebSocketConfig,
+}
+
+pub struct BitstampEventStream {
+    ws_stream: WStream,
+    config: StreamConfig,
+    subscriptions: Vec<types::EventChannel>,
+    /// WebSocket tokens of private subscriptions, reused when resubscribing after a reconnect
+    tokens: HashMap<types::EventChannel, String>,
+    pending: VecDeque<types::Event>,
+    stats: types::StreamStats,
+    history: VecDeque<types::ConnectionEvent>,
+    /// When each channel last had an event
+    last_seen: HashMap<types::EventChannel, chrono::DateTime<chrono::Utc>>,
+    /// Set by `close` or once the connection is gone for good, see `Drop`
+    closed: bool,
+    raw_hook: Option<RawHook>,
+    dedup: Option<Dedup>,
+    commands: mpsc::UnboundedReceiver<Command>,
+    handle: EventStreamHandle,
+}
+
+impl BitstampEventStream {
+    pub(crate) async fn connect(config: StreamConfig) -> Result<BitstampEventStream, Error> {
+        let ws_stream = connect(&config).await?;
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+        let mut stream = BitstampEventStream {
+            ws_stream,
+            config,
+            subscriptions: Vec::new(),
+            tokens: HashMap::new(),
+            pending: VecDeque::new(),
+            stats: types::StreamStats::default(),
+            history: VecDeque::with_capacity(CONNECTION_HISTORY_LEN),
+            last_seen: HashMap::new(),
+            closed: false,
+            raw_hook: None,
+            dedup: None,
+            commands,
+            handle: EventStreamHandle { commands: commands_tx },
+        };
+        stream.record(types::ConnectionEventKind::Connected, None);
+        Ok(stream)
+    }
+
+    fn record(&mut self, kind: types::ConnectionEventKind, reason: Option<String>) {
+        if self.history.len() == CONNECTION_HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(types::ConnectionEvent { kind, at: chrono::Utc::now(), reason });
+    }
+
+    /// Next event from the exchange.
+    ///
+    /// With auto reconnect enabled a dropped connection is reopened and every tracked
+    /// channel resubscribed, then one `EventEvent::Reconnected` event is returned per channel
+    /// before any event from the new connection. Private channels are confirmed first.
+    /// Events sent during the gap are lost, so stateful consumers (e.g. a local order book)
+    /// should resync from a REST snapshot when they see it. Without any tracked channel there
+    /// is no event to return, the reconnect then only shows in `connection_history` and
+    /// `StreamStats::reconnects`.
+    ///
+    /// A rejected private subscription (e.g. after a reconnect with an expired token) is
+    /// returned as an error for which `Error::is_unauthorized` is true. Running out of
+    /// reconnect attempts (`BitstampBuilder::ws_max_reconnect_attempts`) returns one for which
+    /// `Error::is_websocket` is true.
+    ///
+    /// Going without any message for the configured timeout is an error too, use `try_next`
+    /// to tell a quiet channel apart from a broken connection.
+    pub async fn next(&mut self) -> Result<types::Event, Error> {
+        match self.try_next().await? {
+            Some(event) => Ok(event),
+            None => Err(self.inactive()),
+        }
+    }
+
+    /// Like `next`, but returns `Ok(None)` when nothing arrived within the timeout.
+    ///
+    /// The connection stays open and subscribed, so the caller can do other work and poll
+    /// again. Errors are only returned for a connection that is actually broken.
+    pub async fn try_next(&mut self) -> Result<Option<types::Event>, Error> {
+        loop {
+            let event = match self.poll_frame().await? {
+                Some(Frame::Event(event)) => event,
+                Some(Frame::Text(json)) => self.parse(&json)?,
+                None => return Ok(None),
+            };
+            if self.is_duplicate(&event) {
+                continue;
+            }
+            return Ok(Some(event));
+        }
+    }
+
+    /// Like `next`, but leaves decoding text frames to the caller, see `parse`
+    pub(crate) async fn next_frame(&mut self) -> Result<Frame, Error> {
+        self.poll_frame().await?.ok_or_else(|| self.inactive())
+    }
+
+    /// Decode a text frame from `next_frame`
+    pub(crate) fn parse(&mut self, json: &str) -> Result<types::Event, Error> {
+        let event = parse_event(json, &mut self.stats)?;
+        self.seen(&event.channel);
+        Ok(event)
+    }
+
+    /// True if `event` was already returned while dedup is on, see `set_dedup`
+    pub(crate) fn is_duplicate(&mut self, event: &types::Event) -> bool {
+        if self.dedup.is_none() {
+            return false;
+        }
+        match EventKey::of(event) {
+            Some(key) => self.is_duplicate_key(key),
+            None => false,
+        }
+    }
+
+    /// Same as `is_duplicate` for a diff decoded without `parse`
+    pub(crate) fn is_duplicate_diff(&mut self, channel: &types::EventChannel, microtimestamp: u64) -> bool {
+        if self.dedup.is_none() {
+            return false;
+        }
+        self.is_duplicate_key(EventKey { channel: channel.clone(), event: types::EventEvent::Data, id: 0, microtimestamp })
+    }
+
+    fn is_duplicate_key(&mut self, key: EventKey) -> bool {
+        if !self.dedup.as_mut().is_some_and(|dedup| dedup.is_duplicate(&key)) {
+            return false;
+        }
+        self.stats.duplicates += 1;
+        debug!("Dropped duplicate {:?} event on {:?}", key.event, key.channel);
+        true
+    }
+
+    /// Note activity on a channel, for frames decoded without `parse`
+    pub(crate) fn seen(&mut self, channel: &types::EventChannel) {
+        self.last_seen.insert(channel.clone(), chrono::Utc::now());
+    }
+
+    fn inactive(&self) -> Error {
+        text_error(format!("no activity for at least {:?}", self.config.timeout))
+    }
+
+    async fn poll_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(Frame::Event(event)));
+            }
+            let timeout = self.config.timeout;
+            let next = async {
+                tokio::select! {
+                    msg = self.ws_stream.next() => Input::Message(msg),
+                    Some(command) = self.commands.recv() => Input::Command(command),
+                }
+            };
+            match tokio::time::timeout(timeout, next).await {
+                // Timed out
+                Err(_) => return Ok(None),
+                Ok(Input::Command(command)) => self.run_command(command).await,
+                // Didn't time out
+                Ok(Input::Message(next_result)) => match next_result {
+                    Some(Ok(Message::Close(frame))) => {
+                        let reason = match frame {
+                            Some(frame) => format!("closed by server with code {} ({:?})", u16::from(frame.code), frame.reason),
+                            None => "closed by server".to_string(),
+                        };
+                        self.connection_lost(reason).await?;
+                    }
+                    Some(Ok(msg)) => {
+                        match self.read_frame(msg) {
+                            Ok(maybe_msg) => {
+                                if let Some(msg) = maybe_msg {
+                                    return Ok(Some(msg));
+                                } else {
+                                    // Ignore other messages (but they'll reset the timeout)
+                                    continue;
+                                }
+                            }
+                            Err(e) => return Err(e),
+                        };
+                    }
+                    Some(Err(e @ WsError::Capacity(_))) => self.connection_lost(format!("{}, see BitstampBuilder::ws_max_message_size", e)).await?,
+                    Some(Err(e)) => self.connection_lost(e.to_string()).await?,
+                    None => self.connection_lost("connection closed".to_string()).await?,
+                },
+            }
+        }
+    }
+
+    /// Reconnect if enabled, otherwise report the lost connection
+    async fn connection_lost(&mut self, reason: String) -> Result<(), Error> {
+        warn!("WebSocket disconnected from {}: {}", self.config.url, reason);
+        self.record(types::ConnectionEventKind::Disconnected, Some(reason.clone()));
+        if !self.config.auto_reconnect {
+            self.closed = true;
+            return Err(text_error(reason));
+        }
+        // Most disconnects are blips, so the first attempt goes out right away
+        let mut delay = Duration::ZERO;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            info!("WebSocket reconnect attempt {} in {:?}", attempts, delay);
+            tokio::time::sleep(delay).await;
+            match self.reconnect().await {
+                Ok(()) => {
+                    self.stats.reconnects += 1;
+                    self.record(types::ConnectionEventKind::Reconnected, None);
+                    info!("WebSocket reconnected after {} attempt(s), {} channel(s) resubscribed", attempts, self.subscriptions.len());
+                    return self.await_resubscriptions().await;
+                }
+                Err(e) if self.config.max_reconnect_attempts.is_some_and(|max| attempts >= max) => {
+                    error!("WebSocket giving up after {} failed reconnect attempts: {}", attempts, e);
+                    self.closed = true;
+                    return Err(websocket_error(format!("giving up after {} failed reconnect attempts: {}", attempts, e), e));
+                }
+                Err(e) => {
+                    warn!("WebSocket reconnect attempt {} failed: {}", attempts, e);
+                    delay = if delay.is_zero() { self.config.reconnect_delay } else { (delay * 2).min(MAX_RECONNECT_DELAY) };
+                }
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        self.ws_stream = connect(&self.config).await?;
+        for channel in self.subscriptions.clone() {
+            let token = self.tokens.get(&channel).cloned();
+            debug!("Resubscribing {:?}", channel);
+            self.send_subscription(types::EventEvent::BtsSubscribe, channel, token).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the private channels to be confirmed after a reconnect, then queue one
+    /// `Reconnected` event per channel (in subscription order) ahead of anything received
+    /// in the meantime. None without subscriptions, see `next`
+    async fn await_resubscriptions(&mut self) -> Result<(), Error> {
+        let private: Vec<types::EventChannel> = self.subscriptions.iter().filter(|c| self.tokens.contains_key(*c)).cloned().collect();
+        let mut received = VecDeque::new();
+        let confirmed = self.await_confirmations(&private, &mut received, None).await;
+        for channel in self.subscriptions.iter() {
+            self.pending.push_back(types::Event {
+                event: types::EventEvent::Reconnected,
+                channel: channel.clone(),
+                data: types::EventData::Empty {},
+            });
+        }
+        self.pending.append(&mut received);
+        confirmed
+    }
+
+    /// Read until every channel got its `SubscriptionSucceeded`, other events go to `received`.
+    ///
+    /// Everything has to be confirmed within the timeout of the call, not of the last message,
+    /// so a busy connection can't keep it waiting. Without `report` a rejection or the timeout
+    /// fails the call, with one they're recorded in it per channel, as are the confirmations.
+    async fn await_confirmations(
+        &mut self,
+        channels: &[types::EventChannel],
+        received: &mut VecDeque<types::Event>,
+        mut report: Option<&mut types::SubscribeReport>,
+    ) -> Result<(), Error> {
+        let deadline = tokio::time::Instant::now() + self.config.timeout;
+        let mut waiting = channels.to_vec();
+        while !waiting.is_empty() {
+            let next = self.ws_stream.next();
+            let msg = match tokio::time::timeout_at(deadline, next).await {
+                Err(_) => match report {
+                    Some(report) => {
+                        let timeout = self.config.timeout;
+                        report.failed.extend(waiting.drain(..).map(|channel| (channel, error::timeout(timeout))));
+                        break;
+                    }
+                    None => return Err(text_error(format!("no reply to subscription of {:?} for {:?}", waiting, self.config.timeout))),
+                },
+                Ok(None) => return Err(text_error("connection closed".to_string())),
+                Ok(Some(Err(e))) => return Err(text_error_with_inner(format!("failed to subscribe: {}", e), e)),
+                Ok(Some(Ok(msg))) => msg,
+            };
+            let json = match self.read_frame(msg)? {
+                Some(Frame::Text(json)) => json,
+                Some(Frame::Event(event)) => {
+                    received.push_back(event);
+                    continue;
+                }
+                None => continue,
+            };
+            let event = match (self.parse(&json), report.as_deref_mut()) {
+                (Ok(event), _) => event,
+                (Err(e), None) => return Err(e),
+                (Err(e), Some(report)) => {
+                    match failure_channel(&json) {
+                        Some(Some(channel)) => match waiting.iter().position(|c| *c == channel) {
+                            Some(i) => report.failed.push((waiting.remove(i), e)),
+                            None => warn!("Subscription failure for {:?}, which wasn't requested: {}", channel, e),
+                        },
+                        Some(None) => report.failed.push((waiting.remove(0), e)),
+                        // Not a rejection, `parse` already logged it
+                        None => {}
+                    }
+                    continue;
+                }
+            };
+            if event.event == types::EventEvent::SubscriptionSucceeded {
+                if let Some(i) = waiting.iter().position(|c| *c == event.channel) {
+                    let channel = waiting.remove(i);
+                    if let Some(report) = report.as_deref_mut() {
+                        report.subscribed.push(channel);
+                    }
+                    continue;
+                }
+            }
+            received.push_back(event);
+        }
+        Ok(())
+    }
+
+    /// Count a message and pass on its text, control frames give `None`
+    fn read_frame(&mut self, msg: Message) -> Result<Option<Frame>, Error> {
+        self.stats.messages_received += 1;
+        match msg {
+            Message::Binary(bytes) => match String::from_utf8(bytes) {
+                Ok(json) => {
+                    self.capture(&json);
+                    Ok(Some(Frame::Text(json)))
+                }
+                Err(e) => {
+                    self.capture(&String::from_utf8_lossy(e.as_bytes()));
+                    self.stats.parse_failures += 1;
+                    Err(text_error_with_inner(format!("UTF-8 decode failed: {}", e), e))
+                }
+            },
+            Message::Text(t) => {
+                self.capture(&t);
+                Ok(Some(Frame::Text(t)))
+            }
+            Message::Ping(_) => {
+                self.stats.pings += 1;
+                debug!("Ping!");
+                Ok(None)
+            }
+            Message::Close(t) => {
+                debug!("close: {:?}", t);
+                Ok(None)
+            }
+            Message::Pong(_) => {
+                self.stats.pongs += 1;
+                debug!("Pong!");
+                Ok(None)
+            }
+        }
+    }
+
+    pub async fn subscribe(&mut self, channel: types::EventChannel) -> Result<(), Error> {
+        self.send_subscription(types::EventEvent::BtsSubscribe, channel.clone(), None).await?;
+        if !self.subscriptions.contains(&channel) {
+            self.subscriptions.push(channel);
+        }
+        Ok(())
+    }
+
+    /// Subscribe to a private channel with a token from `Bitstamp::get_websocket_token`.
+    ///
+    /// Waits for Bitstamp to confirm the subscription. An invalid or expired token is
+    /// rejected with an error for which `Error::is_unauthorized` is true, get a new token and
+    /// subscribe again. Other events received in the meantime are kept for `next`.
+    pub async fn subscribe_private(&mut self, channel: types::EventChannel, token: &str) -> Result<(), Error> {
+        self.send_subscription(types::EventEvent::BtsSubscribe, channel.clone(), Some(token.to_string())).await?;
+        let mut pending = std::mem::take(&mut self.pending);
+        let confirmed = self.await_confirmations(std::slice::from_ref(&channel), &mut pending, None).await;
+        self.pending = pending;
+        confirmed?;
+        if !self.subscriptions.contains(&channel) {
+            self.subscriptions.push(channel.clone());
+        }
+        self.tokens.insert(channel, token.to_string());
+        Ok(())
+    }
+
+    /// Subscribe to all public `channels` and wait for Bitstamp to confirm each, reporting
+    /// the ones it rejected (e.g. a delisted pair) rather than failing the whole batch.
+    ///
+    /// Rejections that don't name their channel are put on the oldest channel still waiting,
+    /// as replies come in request order. A channel without a reply within the timeout fails
+    /// with an error for which `Error::is_timeout` is true. Only a broken connection fails the
+    /// call itself. Other events received in the meantime are kept for `next`.
+    pub async fn subscribe_all(&mut self, channels: &[types::EventChannel]) -> Result<types::SubscribeReport, Error> {
+        for channel in channels {
+            self.send_subscription(types::EventEvent::BtsSubscribe, channel.clone(), None).await?;
+        }
+        let mut report = types::SubscribeReport::default();
+        let mut pending = std::mem::take(&mut self.pending);
+        let confirmed = self.await_confirmations(channels, &mut pending, Some(&mut report)).await;
+        self.pending = pending;
+        confirmed?;
+        for channel in report.subscribed.iter() {
+            if !self.subscriptions.contains(channel) {
+                self.subscriptions.push(channel.clone());
+            }
+        }
+        Ok(report)
+    }
+
+    pub async fn unsubscribe(&mut self, channel: types::EventChannel) -> Result<(), Error> {
+        self.send_subscription(types::EventEvent::BtsUnsubscribe, channel.clone(), None).await?;
+        self.subscriptions.retain(|c| *c != channel);
+        self.tokens.remove(&channel);
+        self.last_seen.remove(&channel);
+        Ok(())
+    }
+
+    /// Call `hook` with the payload of every text or binary message, before it's decoded
+    /// (binary ones that aren't UTF-8 lossily converted). Meant for capturing exactly what
+    /// the exchange sent when debugging a failing parse, it runs on the read path so keep it
+    /// quick. Replaces any previous hook.
+    pub fn set_raw_hook(&mut self, hook: RawHook) {
+        self.raw_hook = Some(hook);
+    }
+
+    /// Drop events from `next`/`try_next` (and diffs from `MaintainedBook::next`) that were
+    /// already returned, as can happen around a reconnect. Trades are identified by their id,
+    /// orders by id and `microtimestamp`, order books by `microtimestamp`, always per channel
+    /// and event type. Only events within `window` of the newest `microtimestamp` seen are
+    /// remembered. Dropped events are counted in `StreamStats::duplicates`.
+    pub fn set_dedup(&mut self, window: Duration) {
+        self.dedup = Some(Dedup::new(window));
+    }
+
+    fn capture(&self, payload: &str) {
+        if let Some(hook) = &self.raw_hook {
+            hook(payload);
+        }
+    }
+
+    /// A handle for changing subscriptions from elsewhere while this stream is being read
+    pub fn handle(&self) -> EventStreamHandle {
+        self.handle.clone()
+    }
+
+    async fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Subscribe(channel, reply) => {
+                let _ = reply.send(self.subscribe(channel).await);
+            }
+            Command::Unsubscribe(channel, reply) => {
+                let _ = reply.send(self.unsubscribe(channel).await);
+            }
+        }
+    }
+
+    /// Counters since the stream was opened, kept across reconnects
+    pub fn stats(&self) -> types::StreamStats {
+        self.stats
+    }
+
+    /// The last connects, disconnects and reconnects, oldest first
+    pub fn connection_history(&self) -> &VecDeque<types::ConnectionEvent> {
+        &self.history
+    }
+
+    /// How long the connection was down before the latest reconnect, `None` if it never
+    /// reconnected. A resync can often be skipped after a short enough gap.
+    pub fn last_gap(&self) -> Option<Duration> {
+        let reconnected = self.history.iter().rposition(|e| e.kind == types::ConnectionEventKind::Reconnected)?;
+        let disconnected = self.history.iter().take(reconnected).rposition(|e| e.kind == types::ConnectionEventKind::Disconnected)?;
+        (self.history[reconnected].at - self.history[disconnected].at).to_std().ok()
+    }
+
+    /// When each subscribed channel last had an event, `None` for one that had none yet.
+    ///
+    /// The stream timeout only notices a silent connection, this shows a single channel
+    /// going quiet (e.g. a thin market) while others keep the connection busy.
+    pub fn channel_activity(&self) -> HashMap<types::EventChannel, Option<chrono::DateTime<chrono::Utc>>> {
+        self.subscriptions.iter().map(|c| (c.clone(), self.last_seen.get(c).copied())).collect()
+    }
+
+    /// Channels that are resubscribed after a reconnect
+    pub fn subscriptions(&self) -> &[types::EventChannel] {
+        &self.subscriptions
+    }
+
+    async fn send_subscription(&mut self, event: types::EventEvent, channel: types::EventChannel, auth: Option<String>) -> Result<(), Error> {
+        let action = match event {
+            types::EventEvent::BtsUnsubscribe => "unsubscribe",
+            _ => "subscribe",
+        };
+        let msg = Message::Text(serde_json::to_string(&types::OutEvent { event, data: types::OutEventData { channel, auth } }).unwrap());
+        self.send(msg).await.map_err(|e| match e {
+            SendError::Timeout(timeout) => error::timeout(timeout),
+            SendError::Ws(e) => text_error_with_inner(format!("failed to {}: {}", action, e), e),
+        })
+    }
+
+    /// Send and flush a frame, giving up after the write timeout so a wedged socket can't
+    /// block the caller forever
+    async fn send(&mut self, msg: Message) -> Result<(), SendError> {
+        match tokio::time::timeout(self.config.write_timeout, self.ws_stream.send(msg)).await {
+            Ok(result) => result.map_err(SendError::Ws),
+            Err(_) => Err(SendError::Timeout(self.config.write_timeout)),
+        }
+    }
+
+    /// Close the connection, waiting at most the write timeout for the close frame to go out.
+    ///
+    /// Dropping the stream instead just cuts the connection (and logs a warning), as no close
+    /// frame can be sent from `drop`.
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.closed = true;
+        match self.send(Message::Close(None)).await {
+            Ok(()) | Err(SendError::Ws(WsError::ConnectionClosed | WsError::AlreadyClosed)) => Ok(()),
+            Err(SendError::Timeout(timeout)) => Err(error::timeout(timeout)),
+            Err(SendError::Ws(e)) => Err(text_error_with_inner(format!("failed to close: {}", e), e)),
+        }
+    }
+}
+
+impl Drop for BitstampEventStream {
+    fn drop(&mut self) {
+        if !self.closed {
+            warn!("WebSocket to {} dropped without close(), the server sees it as a lost connection", self.config.url);
+        }
+    }
+}
+
+/// What tells two events apart for `BitstampEventStream::set_dedup`
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct EventKey {
+    channel: types::EventChannel,
+    event: types::EventEvent,
+    id: i64,
+    microtimestamp: u64,
+}
+
+impl EventKey {
+    /// `None` for events without a `microtimestamp` (e.g. `Reconnected`), which are never
+    /// duplicates
+    fn of(event: &types::Event) -> Option<EventKey> {
+        let (id, microtimestamp) = match &event.data {
+            types::EventData::Trade { id, microtimestamp, .. }
+            | types::EventData::MyTrade { id, microtimestamp, .. }
+            | types::EventData::Orders { id, microtimestamp, .. } => (*id, microtimestamp),
+            types::EventData::OrderBook { microtimestamp, .. } => (0, microtimestamp),
+            _ => return None,
+        };
+        let microtimestamp = microtimestamp.parse::<u64>().ok()?;
+        Some(EventKey { channel: event.channel.clone(), event: event.event.clone(), id, microtimestamp })
+    }
+}
+
+/// Keys of the events returned within the window, oldest first
+struct Dedup {
+    window: u64,
+    newest: u64,
+    order: VecDeque<EventKey>,
+    seen: HashSet<EventKey>,
+}
+
+impl Dedup {
+    fn new(window: Duration) -> Dedup {
+        Dedup { window: window.as_micros() as u64, newest: 0, order: VecDeque::new(), seen: HashSet::new() }
+    }
+
+    /// Remember `key`, true if it was seen before
+    fn is_duplicate(&mut self, key: &EventKey) -> bool {
+        if self.seen.contains(key) {
+            return true;
+        }
+        let microtimestamp = key.microtimestamp;
+        self.newest = self.newest.max(microtimestamp);
+        while self.order.front().is_some_and(|oldest| oldest.microtimestamp + self.window < self.newest) {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        if microtimestamp + self.window >= self.newest {
+            self.seen.insert(key.clone());
+            self.order.push_back(key.clone());
+        }
+        false
+    }
+}
+
+/// Changes the subscriptions of a `BitstampEventStream` without borrowing it, see
+/// `BitstampEventStream::handle`.
+///
+/// Requests are carried out by the stream's `next()`, so the calls only complete while
+/// something is reading the stream.
+#[derive(Clone)]
+pub struct EventStreamHandle {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl EventStreamHandle {
+    pub async fn subscribe(&self, channel: types::EventChannel) -> Result<(), Error> {
+        self.request(|reply| Command::Subscribe(channel, reply)).await
+    }
+
+    pub async fn unsubscribe(&self, channel: types::EventChannel) -> Result<(), Error> {
+        self.request(|reply| Command::Unsubscribe(channel, reply)).await
+    }
+
+    async fn request(&self, command: impl FnOnce(oneshot::Sender<Result<(), Error>>) -> Command) -> Result<(), Error> {
+        let (reply, result) = oneshot::channel();
+        self.commands.send(command(reply)).map_err(|_| text_error("event stream closed".to_string()))?;
+        result.await.map_err(|_| text_error("event stream closed".to_string()))?
+    }
+}
+
+enum Command {
+    Subscribe(types::EventChannel, oneshot::Sender<Result<(), Error>>),
+    Unsubscribe(types::EventChannel, oneshot::Sender<Result<(), Error>>),
+}
+
+/// An event queued by the stream itself, or the text of a message yet to be decoded
+pub(crate) enum Frame {
+    Event(types::Event),
+    Text(String),
+}
+
+enum Input {
+    Message(Option<Result<Message, WsError>>),
+    Command(Command),
+}
+
+enum SendError {
+    Timeout(Duration),
+    Ws(WsError),
+}
+
+/// Recent trades from REST followed by the live `LiveTrades` channel, see
+/// `Bitstamp::trades_with_backfill`
+pub struct TradeStream {
+    stream: BitstampEventStream,
+    pair: types::CurrencyPairs,
+    backlog: VecDeque<types::Transaction>,
+    last_tid: i64,
+}
+
+impl TradeStream {
+    pub(crate) fn new(stream: BitstampEventStream, pair: types::CurrencyPairs, backfill: Vec<types::Transaction>) -> TradeStream {
+        let last_tid = backfill.iter().filter_map(|t| t.tid.parse().ok()).max().unwrap_or_default();
+        TradeStream { stream, pair, backlog: backfill.into(), last_tid }
+    }
+
+    /// Next trade, oldest first. Live trades already returned by the backfill are skipped.
+    pub async fn next(&mut self) -> Result<types::Transaction, Error> {
+        if let Some(trade) = self.backlog.pop_front() {
+            return Ok(trade);
+        }
+        loop {
+            let event = self.stream.next().await?;
+            if event.channel != types::EventChannel::LiveTrades(self.pair) {
+                continue;
+            }
+            if let types::EventData::Trade { id, amount_str, timestamp, price_str, type_field, .. } = event.data {
+                if id <= self.last_tid {
+                    continue;
+                }
+                self.last_tid = id;
+                return Ok(types::Transaction {
+                    date: timestamp,
+                    tid: id.to_string(),
+                    price: price_str,
+                    type_field: type_field.to_string(),
+                    amount: amount_str,
+                });
+            }
+        }
+    }
+
+    /// The underlying event stream, e.g. for its `stats`
+    pub fn event_stream(&self) -> &BitstampEventStream {
+        &self.stream
+    }
+
+    /// See `BitstampEventStream::close`
+    pub async fn close(self) -> Result<(), Error> {
+        self.stream.close().await
+    }
+}
+
+/// Parse an incoming event. Bitstamp's `bts:error` and `bts:subscription_failed` replies, and
+/// replies of any event whose data has `"status": "error"`, are turned into errors here. Their
+/// channel can be empty so they may not parse as an `Event`.
+fn parse_event(json: &str, stats: &mut types::StreamStats) -> Result<types::Event, Error> {
+    // Typed parse first, the generic value is only needed for errors
+    let parsed = match json::from_str::<types::Event>(json) {
+        Ok(event) if !may_be_error(&event) => {
+            if let Err(e) = event.check_consistency() {
+                stats.parse_failures += 1;
+                warn!("{}.  Original JSON:\n{}", e, json);
+                return Err(text_error(format!("unexpected event data: {}", e)));
+            }
+            return Ok(event);
+        }
+        parsed => parsed,
+    };
+    if let Some(e) = json::from_str::<serde_json::Value>(json).ok().as_ref().and_then(exchange_error) {
+        return Err(e);
+    }
+    parsed.map_err(|e| {
+        stats.parse_failures += 1;
+        warn!("Couldn't deserialize: {:?}.  Original JSON:\n{}", e, json);
+        text_error_with_inner(format!("unable to deserialize: {}", e), e)
+    })
+}
+
+/// Events that `exchange_error` has to look at, all others go through untouched
+fn may_be_error(event: &types::Event) -> bool {
+    matches!(event.event, types::EventEvent::BtsError | types::EventEvent::SubscriptionFailed)
+        || matches!(event.data, types::EventData::Error { .. } | types::EventData::Raw(_))
+}
+
+/// For a frame that `exchange_error` reports, the channel it names if it's a known one
+fn failure_channel(json: &str) -> Option<Option<types::EventChannel>> {
+    let value = json::from_str::<serde_json::Value>(json).ok()?;
+    exchange_error(&value)?;
+    Some(value.get("channel").and_then(|c| serde::Deserialize::deserialize(c).ok()))
+}
+
+/// The error reported by a `bts:error` or `bts:subscription_failed` event, or by an event
+/// carrying `{"status": "error"}` as its data.
+///
+/// It's unauthorized only when its `code` is one of the credential errors, or when it rejects
+/// the subscription of a private channel (the token is what those are checked on). The
+/// message isn't looked at, any other error is a plain one.
+fn exchange_error(value: &serde_json::Value) -> Option<Error> {
+    let event = value.get("event").and_then(|e| e.as_str()).unwrap_or_default();
+    let error_status = value.pointer("/data/status").and_then(|s| s.as_str()) == Some("error");
+    if event != "bts:error" && event != "bts:subscription_failed" && !error_status {
+        return None;
+    }
+    let channel = value.get("channel").and_then(|c| c.as_str()).unwrap_or_default();
+    let message = value
+        .pointer("/data/message")
+        .or_else(|| value.pointer("/data/reason"))
+        .and_then(|m| m.as_str())
+        .unwrap_or(event)
+        .to_string();
+    let code = match value.pointer("/data/code") {
+        Some(serde_json::Value::String(code)) => Some(BitstampErrorCode::from_code(code)),
+        Some(serde_json::Value::Number(code)) => Some(BitstampErrorCode::from_code(&code.to_string())),
+        _ => None,
+    };
+    let subscription = event == "bts:subscription_failed" || event == "bts:subscription_succeeded";
+    let unauthorized_code = matches!(
+        code,
+        Some(BitstampErrorCode::InvalidApiKey | BitstampErrorCode::NoPermission | BitstampErrorCode::InvalidSignature)
+    );
+    if unauthorized_code || (subscription && channel.starts_with("private-")) {
+        return Some(unauthorized(message));
+    }
+    Some(text_error(format!("{} on channel {:?}: {}", event, channel, message)))
+}
+
+async fn connect(config: &StreamConfig) -> Result<WStream, Error> {
+    let url = config.url.as_str();
+    let connected = match &config.tls_connector {
+        None => connect_async_with_config(url, Some(config.ws_config)).await,
+        Some(connector) => connect_with_connector(url, Connector::NativeTls(connector.clone()), config.ws_config).await,
+    };
+    match connected {
+        Ok((ws_stream, _response)) => {
+            info!("WebSocket connected to {}", url);
+            Ok(ws_stream)
+        }
+        Err(e) => {
+            warn!("Failed to connect to {:?}: {:?}", url, e);
+            Err(text_error_with_inner(format!("Failed to connect to {:?}: {:?}", url, e), e))
+        }
+    }
+}
+
+async fn connect_with_connector(url: &str, connector: Connector, ws_config: WebSocketConfig) -> Result<(WStream, WsResponse), WsError> {
+    let uri: hyper::Uri = url.parse().map_err(|_| WsError::Url(UrlError::NoHostName))?;
+    let host = uri.host().ok_or(WsError::Url(UrlError::NoHostName))?;
+    let default_port = if uri.scheme_str() == Some("ws") { 80 } else { 443 };
+    let socket = TcpStream::connect((host, uri.port_u16().unwrap_or(default_port))).await.map_err(WsError::Io)?;
+    client_async_tls_with_config(url, socket, Some(ws_config), Some(connector)).await
+}
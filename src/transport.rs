@@ -0,0 +1,24 @@
+use hyper::{client::HttpConnector, Body, Client, Request, Response};
+use hyper_tls::HttpsConnector;
+use std::future::Future;
+use std::pin::Pin;
+
+pub type TransportError = Box<dyn std::error::Error + Send + Sync>;
+pub type TransportFuture = Pin<Box<dyn Future<Output = Result<Response<Body>, TransportError>> + Send>>;
+
+/// Sends fully built (and already signed) REST requests.
+///
+/// The default is a hyper HTTPS client, replace it through `BitstampBuilder::transport`
+/// to route requests elsewhere or to serve canned responses in tests.
+pub trait Transport: Send + Sync {
+    fn request(&self, request: Request<Body>) -> TransportFuture;
+}
+
+pub type HttpsClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+impl Transport for HttpsClient {
+    fn request(&self, request: Request<Body>) -> TransportFuture {
+        let future = Client::request(self, request);
+        Box::pin(async move { future.await.map_err(|e| Box::new(e) as TransportError) })
+    }
+}
@@ -1,39 +1,271 @@
 use serde::{Deserialize, Serialize, Serializer, Deserializer};
-use serde::de::{self, Visitor, Error};
+use serde::de;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::str::FromStr;
 
+use crate::error::{text_error, text_error_with_inner, Error};
+
+pub use crate::decimal::{Decimal, ParseDecimalError};
+
+/// Decode a REST reply body the way the client does, e.g. one captured earlier or fetched
+/// with another HTTP client. A V2 error body is an `Error` even though no status is known,
+/// its `status()` is then `None`.
+pub fn parse_reply<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, Error> {
+    match crate::reply_status_error(None, body) {
+        Some(e) => Err(e),
+        None => crate::parse_body(body),
+    }
+}
+
+/// `parse_reply` for a reply whose status is known, any non-2xx one is an error too
+pub fn parse_reply_with_status<T: serde::de::DeserializeOwned>(status: hyper::StatusCode, body: &str) -> Result<T, Error> {
+    match crate::reply_error(status, body) {
+        Some(e) => Err(e),
+        None => crate::parse_body(body),
+    }
+}
+
+/// Bitstamp's ticker has no sizes for the best bid and ask, get those from `get_order_book`
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Ticker {
+    #[serde(deserialize_with = "field::high")]
     pub high: String,
+    #[serde(deserialize_with = "field::last")]
     pub last: String,
     pub timestamp: String,
+    #[serde(deserialize_with = "field::bid")]
     pub bid: String,
+    #[serde(deserialize_with = "field::vwap")]
     pub vwap: String,
+    #[serde(deserialize_with = "field::volume")]
     pub volume: String,
+    #[serde(deserialize_with = "field::low")]
     pub low: String,
+    #[serde(deserialize_with = "field::ask")]
     pub ask: String,
+    #[serde(deserialize_with = "field::open")]
     pub open: String,
+    /// Direction of the last trade, `"0"` buy or `"1"` sell, see `Ticker::last_side`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    /// Price 24 hours ago
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_24: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent_change_24: Option<String>,
+}
+
+impl Ticker {
+    pub fn last_side(&self) -> Option<TradeSide> {
+        match self.side.as_deref() {
+            Some("0") => Some(TradeSide::Buy),
+            Some("1") => Some(TradeSide::Sell),
+            _ => None,
+        }
+    }
+
+    pub fn open_24(&self) -> Option<Decimal> {
+        self.open_24.as_deref().and_then(|s| Decimal::from_str(s).ok())
+    }
+
+    pub fn percent_change_24(&self) -> Option<Decimal> {
+        self.percent_change_24.as_deref().and_then(|s| Decimal::from_str(s).ok())
+    }
+}
+
+/// Element of the all-pairs `ticker/` reply
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairTicker {
+    /// e.g. `"BTC/USD"`, empty when parsed from a single pair `ticker/{pair}/` reply
+    #[serde(default)]
+    pub pair: String,
+    #[serde(flatten)]
+    pub ticker: Ticker,
+}
+
+impl PairTicker {
+    /// The pair in url symbol form, `"BTC/USD"` becomes `"btcusd"`
+    pub fn url_symbol(&self) -> String {
+        self.pair.replace('/', "").to_lowercase()
+    }
+}
+
+/// Reply of either ticker endpoint, the `ticker/` array or a single `ticker/{pair}/` object
+#[derive(Default, Debug, Clone, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Tickers(pub Vec<PairTicker>);
+
+impl<'de> Deserialize<'de> for Tickers {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Shape {
+            Many(Vec<PairTicker>),
+            One(Box<PairTicker>),
+        }
+
+        match Shape::deserialize(deserializer)? {
+            Shape::Many(tickers) => Ok(Tickers(tickers)),
+            Shape::One(ticker) => Ok(Tickers(vec![*ticker])),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OrderBook {
     pub timestamp: String,
     pub microtimestamp: String,
+    #[serde(deserialize_with = "field::bids")]
     pub bids: Vec<Vec<String>>,
+    #[serde(deserialize_with = "field::asks")]
     pub asks: Vec<Vec<String>>,
 }
 
+impl OrderBook {
+    /// Parse the string levels into `Decimal` price levels
+    pub fn typed(&self) -> Result<OrderBookTyped, ParseDecimalError> {
+        Ok(OrderBookTyped {
+            timestamp: self.timestamp.clone(),
+            microtimestamp: self.microtimestamp.clone(),
+            bids: parse_levels(&self.bids)?,
+            asks: parse_levels(&self.asks)?,
+        })
+    }
+}
+
+fn parse_levels(levels: &[Vec<String>]) -> Result<Vec<PriceLevel>, ParseDecimalError> {
+    levels.iter().map(|level| PriceLevel::from_strings(level)).collect()
+}
+
+/// A single order book level, ordered by price and then amount
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    pub amount: Decimal,
+}
+
+impl PriceLevel {
+    /// Parse a `[price, amount, ...]` level as sent by Bitstamp, extra elements are ignored
+    pub fn from_strings(level: &[String]) -> Result<PriceLevel, ParseDecimalError> {
+        let field = |i: usize| level.get(i).map(|s| s.as_str()).unwrap_or_default();
+        Ok(PriceLevel {
+            price: Decimal::from_str(field(0))?,
+            amount: Decimal::from_str(field(1))?,
+        })
+    }
+}
+
+/// Result of `OrderBookTyped::estimate_fill`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillEstimate {
+    /// Amount the book can fill
+    pub filled: Decimal,
+    /// Amount left over because the book ran out of levels
+    pub unfilled: Decimal,
+    /// Total price of the filled amount, in the counter currency
+    pub cost: Decimal,
+    /// Volume weighted price of the filled amount, `None` when nothing could be filled
+    pub average_price: Option<Decimal>,
+    /// Price of the last level touched
+    pub worst_price: Option<Decimal>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderBookTyped {
+    pub timestamp: String,
+    pub microtimestamp: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+impl OrderBookTyped {
+    /// Highest bid
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.iter().max_by_key(|level| level.price)
+    }
+
+    /// Lowest ask
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.iter().min_by_key(|level| level.price)
+    }
+
+    /// Best ask minus best bid, `None` when either side is empty
+    pub fn spread(&self) -> Option<Decimal> {
+        self.best_ask()?.price.checked_sub(self.best_bid()?.price)
+    }
+
+    /// Midpoint between best bid and best ask, `None` when either side is empty
+    pub fn mid_price(&self) -> Option<Decimal> {
+        self.best_bid()?.price.checked_add(self.best_ask()?.price)?.checked_div(Decimal::new(2, 0))
+    }
+
+    /// Spread relative to the mid price in basis points, `None` when either side is empty
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        self.spread()?.checked_mul(Decimal::new(10_000, 0))?.checked_div(self.mid_price()?)
+    }
+
+    /// Walk the asks (for a buy) or the bids (for a sell) best price first, as a market order
+    /// for `amount` would. When the book is too thin the part that couldn't be filled is left
+    /// in `FillEstimate::unfilled`, as is anything from a level whose cost would overflow.
+    pub fn estimate_fill(&self, side: TradeSide, amount: Decimal) -> FillEstimate {
+        let mut levels: Vec<&PriceLevel> = match side {
+            TradeSide::Buy => self.asks.iter().collect(),
+            TradeSide::Sell => self.bids.iter().collect(),
+        };
+        match side {
+            TradeSide::Buy => levels.sort_by_key(|level| level.price),
+            TradeSide::Sell => levels.sort_by_key(|level| std::cmp::Reverse(level.price)),
+        }
+
+        let mut estimate = FillEstimate { unfilled: amount, ..Default::default() };
+        for level in levels {
+            if !estimate.unfilled.is_sign_positive() {
+                break;
+            }
+            if !level.amount.is_sign_positive() {
+                continue;
+            }
+            let taken = level.amount.min(estimate.unfilled);
+            let totals = taken.checked_mul(level.price).and_then(|cost| {
+                Some((estimate.filled.checked_add(taken)?, estimate.unfilled.checked_sub(taken)?, estimate.cost.checked_add(cost)?))
+            });
+            let (filled, unfilled, cost) = match totals {
+                Some(totals) => totals,
+                None => break,
+            };
+            estimate.filled = filled;
+            estimate.unfilled = unfilled;
+            estimate.cost = cost;
+            estimate.worst_price = Some(level.price);
+        }
+        estimate.average_price = estimate.cost.checked_div(estimate.filled);
+        estimate
+    }
+
+    /// Sort bids best (highest) first and asks best (lowest) first
+    pub fn sort(&mut self) {
+        self.bids.sort_by(|a, b| b.cmp(a));
+        self.asks.sort();
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub date: String,
     pub tid: String,
+    #[serde(deserialize_with = "field::price")]
     pub price: String,
     #[serde(rename = "type")]
     pub type_field: String,
+    #[serde(deserialize_with = "field::amount")]
     pub amount: String,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Time {
     Minute,
@@ -41,10 +273,33 @@ pub enum Time {
     Day,
 }
 
+impl Time {
+    /// Wire form, as used in the `time` query parameter and by serde
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Time::Minute => "minute",
+            Time::Hour => "hour",
+            Time::Day => "day",
+        }
+    }
+}
 
 impl std::fmt::Display for Time {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Time {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Time, String> {
+        match s.to_lowercase().as_str() {
+            "minute" => Ok(Time::Minute),
+            "hour" => Ok(Time::Hour),
+            "day" => Ok(Time::Day),
+            _ => Err(format!("Unknown time: {}", s)),
+        }
     }
 }
 
@@ -59,12 +314,58 @@ pub struct PairInfo {
     pub description: String,
 }
 
+/// Pair metadata joined with its current ticker
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketOverview {
+    pub url_symbol: String,
+    pub name: String,
+    pub base_decimals: i64,
+    pub counter_decimals: i64,
+    pub minimum_order: String,
+    pub enabled: bool,
+    /// Last trade price, `None` if no ticker was returned for the pair
+    pub last: Option<String>,
+    /// Last 24 hours volume
+    pub volume: Option<String>,
+}
+
+impl PairInfo {
+    /// Truncate an amount (in the base currency) to `base_decimals`, toward zero so the
+    /// result never exceeds the amount passed in
+    pub fn round_amount(&self, amount: Decimal) -> Decimal {
+        amount.trunc(self.base_decimals.max(0) as u32)
+    }
+
+    /// Round a price (in the counter currency) to `counter_decimals`, to the nearest value
+    /// with halves rounded away from zero
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        price.round_dp(self.counter_decimals.max(0) as u32)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConversionRate {
     pub sell: String,
     pub buy: String,
 }
 
+impl ConversionRate {
+    pub fn buy_decimal(&self) -> Result<Decimal, ParseDecimalError> {
+        Decimal::from_str(&self.buy)
+    }
+
+    pub fn sell_decimal(&self) -> Result<Decimal, ParseDecimalError> {
+        Decimal::from_str(&self.sell)
+    }
+
+    /// Buy rate minus sell rate, an error if either doesn't parse or the difference overflows
+    pub fn spread(&self) -> Result<Decimal, Error> {
+        let buy = self.buy_decimal().map_err(|e| text_error_with_inner(format!("invalid buy rate: {}", e), e))?;
+        let sell = self.sell_decimal().map_err(|e| text_error_with_inner(format!("invalid sell rate: {}", e), e))?;
+        buy.checked_sub(sell).ok_or_else(|| text_error(format!("spread between {} and {} overflows", buy, sell)))
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Empty {}
 
@@ -74,49 +375,723 @@ pub struct Offset {
     pub offset: String
 }
 
+/// Declares `AccountBalance` with every named field read through `decimal_string`
+macro_rules! account_balance {
+    ($($field:ident),* $(,)?) => {
+        /// Currencies missing from the reply (e.g. never held by the account) are left empty
+        #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct AccountBalance {
+            $(
+                #[serde(deserialize_with = "decimal_string")]
+                pub $field: String,
+            )*
+            /// Fields without a member above, e.g. currencies added since or margin fields of
+            /// margin accounts. Numbers are kept in string form too.
+            #[serde(flatten, deserialize_with = "string_map")]
+            pub extra: HashMap<String, String>,
+        }
+    };
+}
+
+account_balance!(
+    bch_available, bch_balance, bch_reserved, bch_withdrawal_fee, bchbtc_fee, bcheur_fee,
+    bchusd_fee, btc_available, btc_balance, btc_reserved, btc_withdrawal_fee, btceur_fee,
+    btcusd_fee, eth_available, eth_balance, eth_reserved, eth_withdrawal_fee, ethbtc_fee,
+    etheur_fee, ethusd_fee, eur_available, eur_balance, eur_reserved, eurusd_fee,
+    ltc_available, ltc_balance, ltc_reserved, ltc_withdrawal_fee, ltcbtc_fee, ltceur_fee,
+    ltcusd_fee, usd_available, usd_balance, usd_reserved, xrp_available, xrp_balance,
+    xrp_reserved, xrp_withdrawal_fee, xrpbtc_fee, xrpeur_fee, xrpusd_fee
+);
+
+impl AccountBalance {
+    /// Trading fee for a pair, `None` if the reply didn't include it
+    pub fn fee(&self, pair: &CurrencyPairs) -> Option<&str> {
+        let fee = match pair {
+            CurrencyPairs::Btcusd => &self.btcusd_fee,
+            CurrencyPairs::Btceur => &self.btceur_fee,
+            CurrencyPairs::Eurusd => &self.eurusd_fee,
+            CurrencyPairs::Xrpusd => &self.xrpusd_fee,
+            CurrencyPairs::Xrpeur => &self.xrpeur_fee,
+            CurrencyPairs::Xrpbtc => &self.xrpbtc_fee,
+            CurrencyPairs::Ltcusd => &self.ltcusd_fee,
+            CurrencyPairs::Ltceur => &self.ltceur_fee,
+            CurrencyPairs::Ltcbtc => &self.ltcbtc_fee,
+            CurrencyPairs::Ethusd => &self.ethusd_fee,
+            CurrencyPairs::Etheur => &self.etheur_fee,
+            CurrencyPairs::Ethbtc => &self.ethbtc_fee,
+            CurrencyPairs::Bchusd => &self.bchusd_fee,
+            CurrencyPairs::Bcheur => &self.bcheur_fee,
+            CurrencyPairs::Bchbtc => &self.bchbtc_fee,
+        };
+        if fee.is_empty() {
+            None
+        } else {
+            Some(fee.as_str())
+        }
+    }
+
+    /// Trading fee for a pair as a percentage, `None` if missing or not a number
+    pub fn fee_decimal(&self, pair: &CurrencyPairs) -> Option<Decimal> {
+        self.fee(pair).and_then(|fee| Decimal::from_str(fee).ok())
+    }
+
+    /// Value of all balances in `quote` (e.g. `"usd"`) at the `last` price of `tickers`, as
+    /// returned by `Bitstamp::get_all_tickers`. Pairs are used in either direction and
+    /// chained where there's no direct one (ETH to USD through EUR, for example), taking the
+    /// fewest hops. Fails for a non-zero balance that can't be converted.
+    pub fn total_in(&self, quote: &str, tickers: &[PairTicker]) -> Result<Decimal, Error> {
+        let quote = quote.to_lowercase();
+        let balances = [
+            ("bch", &self.bch_balance),
+            ("btc", &self.btc_balance),
+            ("eth", &self.eth_balance),
+            ("eur", &self.eur_balance),
+            ("ltc", &self.ltc_balance),
+            ("usd", &self.usd_balance),
+            ("xrp", &self.xrp_balance),
+        ];
+        let rates = conversion_rates(tickers);
+        let mut total = Decimal::ZERO;
+        for (currency, balance) in balances.iter() {
+            // Missing from the reply
+            if balance.is_empty() {
+                continue;
+            }
+            let amount = Decimal::from_str(balance)
+                .map_err(|e| text_error_with_inner(format!("invalid {} balance {:?}: {}", currency, balance, e), e))?;
+            if amount.is_zero() {
+                continue;
+            }
+            let value = convert(amount, currency, &quote, &rates)
+                .ok_or_else(|| text_error(format!("no ticker to convert {} to {}", currency, quote)))?;
+            total = total
+                .checked_add(value)
+                .ok_or_else(|| text_error(format!("total in {} overflows adding {} {}", quote, balance, currency)))?;
+        }
+        Ok(total)
+    }
+}
+
+/// One currency of the balance reply, see `Bitstamp::get_balance_filtered`
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CurrencyBalance {
+    pub available: String,
+    pub balance: String,
+    pub reserved: String,
+    /// Not sent for fiat currencies
+    pub withdrawal_fee: Option<String>,
+}
+
+impl CurrencyBalance {
+    /// The `{currency}_…` fields of a balance reply, `None` if it has no `{currency}_balance`
+    pub(crate) fn from_reply(reply: &HashMap<String, serde_json::Value>, currency: &str) -> Option<CurrencyBalance> {
+        let field = |name: &str| {
+            reply.get(&format!("{}_{}", currency, name)).map(|value| match value {
+                serde_json::Value::String(s) => normalize_number(s.clone()),
+                other => normalize_number(other.to_string()),
+            })
+        };
+        Some(CurrencyBalance {
+            balance: field("balance")?,
+            available: field("available").unwrap_or_default(),
+            reserved: field("reserved").unwrap_or_default(),
+            withdrawal_fee: field("withdrawal_fee"),
+        })
+    }
+}
+
+/// Per currency, the currencies it converts to directly: (to, last price, whether the
+/// price has to be inverted)
+fn conversion_rates(tickers: &[PairTicker]) -> HashMap<String, Vec<(String, Decimal, bool)>> {
+    let mut rates: HashMap<String, Vec<(String, Decimal, bool)>> = HashMap::new();
+    for ticker in tickers {
+        let (base, counter) = match ticker.pair.split_once('/') {
+            Some((base, counter)) => (base.to_lowercase(), counter.to_lowercase()),
+            None => continue,
+        };
+        let last = match Decimal::from_str(&ticker.ticker.last) {
+            Ok(last) if last.is_sign_positive() => last,
+            _ => continue,
+        };
+        rates.entry(base.clone()).or_default().push((counter.clone(), last, false));
+        rates.entry(counter).or_default().push((base, last, true));
+    }
+    rates
+}
+
+/// Breadth first, so the path with the fewest conversions wins
+fn convert(amount: Decimal, from: &str, to: &str, rates: &HashMap<String, Vec<(String, Decimal, bool)>>) -> Option<Decimal> {
+    let mut queue = VecDeque::from(vec![(from.to_string(), amount)]);
+    let mut seen = HashSet::new();
+    seen.insert(from.to_string());
+    while let Some((currency, value)) = queue.pop_front() {
+        if currency == to {
+            return Some(value);
+        }
+        for (next, last, inverse) in rates.get(&currency).into_iter().flatten() {
+            if !seen.insert(next.clone()) {
+                continue;
+            }
+            let converted = if *inverse { value.checked_div(*last)? } else { value.checked_mul(*last)? };
+            queue.push_back((next.clone(), converted));
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+impl std::fmt::Display for TradeSide {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TradeSide::Buy => write!(f, "buy"),
+            TradeSide::Sell => write!(f, "sell"),
+        }
+    }
+}
+
+/// How long a limit order stays on the book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TimeInForce {
+    #[default]
+    GoodTillCancelled,
+    /// Cancelled at midnight UTC (`daily_order`)
+    Day,
+    /// Fill what's possible right away, cancel the rest (`ioc_order`)
+    ImmediateOrCancel,
+    /// Fill completely right away or cancel (`fok_order`)
+    FillOrKill,
+    /// Cancelled at the given unix timestamp (`gtd_order` with `expire_time`)
+    GoodTillDate(i64),
+}
+
+/// Serialized as the order flags Bitstamp expects, nothing for `GoodTillCancelled`
+impl Serialize for TimeInForce {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            TimeInForce::GoodTillCancelled => {}
+            TimeInForce::Day => map.serialize_entry("daily_order", &true)?,
+            TimeInForce::ImmediateOrCancel => map.serialize_entry("ioc_order", &true)?,
+            TimeInForce::FillOrKill => map.serialize_entry("fok_order", &true)?,
+            TimeInForce::GoodTillDate(expire_time) => {
+                map.serialize_entry("gtd_order", &true)?;
+                map.serialize_entry("expire_time", expire_time)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitOrderRequest {
+    pub amount: String,
+    pub price: String,
+    /// Client side id, Bitstamp rejects a second order with the same one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    #[serde(flatten, skip_deserializing)]
+    pub time_in_force: TimeInForce,
+    /// Maker only (`moc_order`, maker or cancel). An order that would match immediately is
+    /// rejected with an error for which `Error::is_post_only_rejected` is true. Can't be
+    /// combined with `ImmediateOrCancel` or `FillOrKill`.
+    #[serde(rename = "moc_order", default, skip_serializing_if = "is_false")]
+    pub post_only: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketOrderRequest {
+    pub amount: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+}
+
+/// One order of a `Bitstamp::place_orders` batch
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderRequest {
+    Limit { side: TradeSide, currency_pair: String, request: LimitOrderRequest },
+    Market { side: TradeSide, currency_pair: String, request: MarketOrderRequest },
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub datetime: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(deserialize_with = "field::price")]
+    pub price: String,
+    #[serde(deserialize_with = "field::amount")]
+    pub amount: String,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+/// Order still resting on the book, from `open_orders`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenOrder {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub datetime: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(deserialize_with = "field::price")]
+    pub price: String,
+    #[serde(deserialize_with = "field::amount")]
+    pub amount: String,
+    /// e.g. `"BTC/USD"`
+    pub currency_pair: String,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+/// Balances and open orders fetched together, see `Bitstamp::account_snapshot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountSnapshot {
+    pub balance: AccountBalance,
+    pub open_orders: Vec<OpenOrder>,
+    /// When the requests were sent
+    pub captured_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CancelOrderRequest {
+    pub id: String,
+}
+
+/// The cancelled order. What else is echoed back depends on the order, a cancelled market
+/// order has no `price`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CancelResult {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(default, deserialize_with = "opt_decimal_string")]
+    pub amount: Option<String>,
+    #[serde(default, deserialize_with = "opt_decimal_string")]
+    pub price: Option<String>,
+    /// 0 for a buy, 1 for a sell
+    #[serde(rename = "type", default, deserialize_with = "opt_string_or_number")]
+    pub type_field: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderStatusRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderStatus {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(default)]
+    pub datetime: String,
+    #[serde(rename = "type", default, deserialize_with = "string_or_number")]
+    pub type_field: String,
+    pub status: String,
+    #[serde(default)]
+    pub market: String,
+    #[serde(default)]
+    pub transactions: Vec<OrderTransaction>,
+    #[serde(default, deserialize_with = "decimal_string")]
+    pub amount_remaining: String,
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+}
+
+impl OrderStatus {
+    /// Amount filled so far, the `transactions` summed in the base currency of `market`
+    pub fn filled_amount(&self) -> Result<Decimal, Error> {
+        let base = self.market.split('/').next().unwrap_or_default().to_lowercase();
+        let mut filled = Decimal::ZERO;
+        for transaction in &self.transactions {
+            let amount = match transaction.amounts.get(&base) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(serde_json::Value::Number(n)) => n.to_string(),
+                _ => continue,
+            };
+            let amount = Decimal::from_str(&amount)
+                .map_err(|e| text_error_with_inner(format!("invalid {} amount {:?} in transaction {}: {}", base, amount, transaction.tid, e), e))?;
+            filled = filled.checked_add(amount.abs()).ok_or_else(|| text_error(format!("filled amount of order {} overflows", self.id)))?;
+        }
+        Ok(filled)
+    }
+
+    /// The order as placed, with the amount remaining plus filled and the price of the last
+    /// fill (empty without fills)
+    pub(crate) fn placed_order(self) -> Result<Order, Error> {
+        let remaining = match self.amount_remaining.as_str() {
+            "" => Decimal::ZERO,
+            remaining => Decimal::from_str(remaining)
+                .map_err(|e| text_error_with_inner(format!("invalid amount_remaining {:?} of order {}: {}", remaining, self.id, e), e))?,
+        };
+        let amount = remaining
+            .checked_add(self.filled_amount()?)
+            .ok_or_else(|| text_error(format!("amount of order {} overflows", self.id)))?;
+        let price = self.transactions.last().map(|transaction| transaction.price.clone()).unwrap_or_default();
+        Ok(Order {
+            id: self.id,
+            datetime: self.datetime,
+            type_field: self.type_field,
+            price,
+            amount: amount.to_string(),
+            client_order_id: self.client_order_id,
+        })
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderTransaction {
+    #[serde(deserialize_with = "string_or_number")]
+    pub tid: String,
+    #[serde(deserialize_with = "decimal_string")]
+    pub price: String,
+    #[serde(deserialize_with = "decimal_string")]
+    pub fee: String,
+    pub datetime: String,
+    #[serde(rename = "type", deserialize_with = "string_or_number")]
+    pub type_field: String,
+    /// Per currency amounts, keyed on the currency code
+    #[serde(flatten)]
+    pub amounts: std::collections::HashMap<String, serde_json::Value>,
+}
+
+/// Bitstamp is inconsistent about quoting numbers, accept both and keep the string form
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        other => Err(de::Error::custom(format!("expected a string or number, got {}", other))),
+    }
+}
+
+/// A map of string or number values, `null`s are dropped
+fn string_map<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, String>, D::Error> {
+    let mut map = HashMap::new();
+    for (key, value) in HashMap::<String, serde_json::Value>::deserialize(deserializer)? {
+        match value {
+            serde_json::Value::String(s) => map.insert(key, s),
+            serde_json::Value::Number(n) => map.insert(key, n.to_string()),
+            serde_json::Value::Bool(b) => map.insert(key, b.to_string()),
+            serde_json::Value::Null => None,
+            other => return Err(de::Error::custom(format!("expected a string or number for {}, got {}", key, other))),
+        };
+    }
+    Ok(map)
+}
+
+/// An amount, price or fee, see `normalize_number`
+fn decimal_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    string_or_number(deserializer).map(normalize_number)
+}
+
+fn opt_decimal_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    opt_string_or_number(deserializer).map(|s| s.map(normalize_number))
+}
+
+/// With the `normalize-decimals` feature numbers read into strings are put in canonical
+/// form, so the same value compares equal whichever endpoint it came from: `"1.50000000"`
+/// and `"1.5"` both become `"1.5"`. This is lossless, only trailing zeros (and exponents)
+/// go. Ids and other non-amount fields are never touched.
+///
+/// Normalized are the `Ticker` prices and volume, `OrderBook` levels, the price and amount
+/// of `Transaction`, `Order`, `OpenOrder`, `CancelResult` and `OrderTransaction` (plus its
+/// fee), `OrderStatus::amount_remaining`, the named `AccountBalance` fields (not `extra`),
+/// `CurrencyBalance`, `UserTransaction::fee` and `CryptoTransaction::amount`.
+#[cfg(feature = "normalize-decimals")]
+fn normalize_number(s: String) -> String {
+    match Decimal::from_str(&s) {
+        Ok(d) => d.normalize().to_string(),
+        Err(_) => s,
+    }
+}
+
+#[cfg(not(feature = "normalize-decimals"))]
+fn normalize_number(s: String) -> String {
+    s
+}
+
+/// With the `strict-decimals` feature a `field` that should be a decimal but isn't fails
+/// to deserialize right away, naming the field, instead of when it's parsed later
+#[cfg(feature = "strict-decimals")]
+fn check_decimal<E: de::Error>(field: &str, s: &str) -> Result<(), E> {
+    match Decimal::from_str(s) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(E::custom(format!("{} is not a decimal: {:?}", field, s))),
+    }
+}
+
+#[cfg(not(feature = "strict-decimals"))]
+fn check_decimal<E: de::Error>(_field: &str, _s: &str) -> Result<(), E> {
+    Ok(())
+}
+
+fn checked_decimal_string<'de, D: Deserializer<'de>>(deserializer: D, field: &str) -> Result<String, D::Error> {
+    let s = decimal_string(deserializer)?;
+    check_decimal(field, &s)?;
+    Ok(s)
+}
+
+/// Order book levels, each at least a price and an amount
+fn checked_levels<'de, D: Deserializer<'de>>(deserializer: D, field: &str) -> Result<Vec<Vec<String>>, D::Error> {
+    let mut levels = Vec::<Vec<String>>::deserialize(deserializer)?;
+    if cfg!(feature = "normalize-decimals") {
+        for value in levels.iter_mut().flatten() {
+            *value = normalize_number(std::mem::take(value));
+        }
+    }
+    if cfg!(feature = "strict-decimals") {
+        for level in &levels {
+            if level.len() < 2 {
+                return Err(de::Error::custom(format!("{} has a level without price and amount: {:?}", field, level)));
+            }
+            for value in level {
+                check_decimal(field, value)?;
+            }
+        }
+    }
+    Ok(levels)
+}
+
+macro_rules! decimal_fields {
+    ($($field:ident),* $(,)?) => {
+        $(
+            pub(super) fn $field<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+                super::checked_decimal_string(deserializer, stringify!($field))
+            }
+        )*
+    };
+}
+
+/// Deserializers that know the name of their field, for `check_decimal` errors
+mod field {
+    use serde::Deserializer;
+
+    decimal_fields!(high, last, bid, vwap, volume, low, ask, open, price, amount);
+
+    pub(super) fn bids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<String>>, D::Error> {
+        super::checked_levels(deserializer, "bids")
+    }
+
+    pub(super) fn asks<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Vec<String>>, D::Error> {
+        super::checked_levels(deserializer, "asks")
+    }
+}
+
+/// A float sent as a JSON number. Numeric strings are accepted too, so a quoted value
+/// doesn't fail the whole event.
+fn number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(n) => n.as_f64().ok_or_else(|| de::Error::custom(format!("{} is not a float", n))),
+        serde_json::Value::String(s) => s.parse().map_err(|_| de::Error::custom(format!("expected a number, got {:?}", s))),
+        other => Err(de::Error::custom(format!("expected a number, got {}", other))),
+    }
+}
+
+fn opt_string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(s)) => Ok(Some(s)),
+        Some(serde_json::Value::Number(n)) => Ok(Some(n.to_string())),
+        Some(other) => Err(de::Error::custom(format!("expected a string or number, got {}", other))),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    Asc,
+    Desc,
+}
+
+/// Paging and filtering for `user_transactions`, unset fields use Bitstamp's defaults
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserTransactionsRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    /// At most 1000
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Sort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since_id: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserTransaction {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub datetime: String,
+    /// 0 deposit, 1 withdrawal, 2 market trade, 14 sub account transfer
+    #[serde(rename = "type", deserialize_with = "string_or_number")]
+    pub type_field: String,
+    #[serde(deserialize_with = "decimal_string")]
+    pub fee: String,
+    #[serde(default)]
+    pub order_id: Option<serde_json::Value>,
+    /// Per currency amounts (e.g. `"btc"`, `"usd"`) and trade rates (e.g. `"btc_usd"`)
+    #[serde(flatten)]
+    pub amounts: std::collections::HashMap<String, serde_json::Value>,
+}
+
+impl UserTransaction {
+    /// Currency the `fee` is charged in.
+    ///
+    /// Trades carry a rate field like `"btc_usd"` and are charged in its counter currency
+    /// (`"usd"`), deposits and withdrawals in the single currency that moved. `None` when
+    /// neither can be told from the amounts.
+    pub fn fee_currency(&self) -> Option<&str> {
+        let rate = self.amounts.keys().find_map(|key| {
+            let (base, counter) = key.split_once('_')?;
+            if self.amounts.contains_key(base) && self.amounts.contains_key(counter) {
+                Some(counter)
+            } else {
+                None
+            }
+        });
+        if rate.is_some() {
+            return rate;
+        }
+        let mut moved = self.amounts.iter().filter(|(key, value)| {
+            key.chars().all(|c| c.is_ascii_alphanumeric()) && amount_decimal(value).is_some_and(|a| !a.is_zero())
+        });
+        match (moved.next(), moved.next()) {
+            (Some((currency, _)), None) => Some(currency.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn fee_decimal(&self) -> Result<Decimal, ParseDecimalError> {
+        Decimal::from_str(&self.fee)
+    }
+}
+
+/// Amounts in the flattened maps come as strings or numbers
+fn amount_decimal(value: &serde_json::Value) -> Option<Decimal> {
+    match value {
+        serde_json::Value::String(s) => Decimal::from_str(s).ok(),
+        serde_json::Value::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+        _ => None,
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebsocketToken {
+    pub token: String,
+    pub user_id: i64,
+    /// Seconds the token stays valid for
+    pub valid_sec: i64,
+}
+
+/// Currencies whose deposits and withdrawals need a destination tag (XRP) or memo (XLM, HBAR)
+/// on top of the address. Sending without it can lose the funds.
+pub fn requires_destination_tag(currency: &str) -> bool {
+    matches!(currency.to_lowercase().as_str(), "xrp" | "xlm" | "hbar")
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepositAddress {
+    pub address: String,
+    /// Destination tag or memo, always set for currencies where `requires_destination_tag` is true
+    #[serde(default, alias = "memo_id", deserialize_with = "opt_string_or_number")]
+    pub destination_tag: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalRequest {
+    pub amount: String,
+    pub address: String,
+    /// Destination tag or memo, required for currencies where `requires_destination_tag` is true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination_tag: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalResult {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+}
+
+/// Paging for `crypto_transactions`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CryptoTransactionsRequest {
+    /// At most 1000
+    pub limit: u32,
+    pub offset: u32,
+    /// Also return Ripple IOU transactions, only sent when true
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub include_ious: bool,
+}
+
+/// On-chain deposit or withdrawal
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct AccountBalance {
-    pub bch_available: String,
-    pub bch_balance: String,
-    pub bch_reserved: String,
-    pub bch_withdrawal_fee: String,
-    pub bchbtc_fee: String,
-    pub bcheur_fee: String,
-    pub bchusd_fee: String,
-    pub btc_available: String,
-    pub btc_balance: String,
-    pub btc_reserved: String,
-    pub btc_withdrawal_fee: String,
-    pub btceur_fee: String,
-    pub btcusd_fee: String,
-    pub eth_available: String,
-    pub eth_balance: String,
-    pub eth_reserved: String,
-    pub eth_withdrawal_fee: String,
-    pub ethbtc_fee: String,
-    pub etheur_fee: String,
-    pub ethusd_fee: String,
-    pub eur_available: String,
-    pub eur_balance: String,
-    pub eur_reserved: String,
-    pub eurusd_fee: String,
-    pub ltc_available: String,
-    pub ltc_balance: String,
-    pub ltc_reserved: String,
-    pub ltc_withdrawal_fee: String,
-    pub ltcbtc_fee: String,
-    pub ltceur_fee: String,
-    pub ltcusd_fee: String,
-    pub usd_available: String,
-    pub usd_balance: String,
-    pub usd_reserved: String,
-    pub xrp_available: String,
-    pub xrp_balance: String,
-    pub xrp_reserved: String,
-    pub xrp_withdrawal_fee: String,
-    pub xrpbtc_fee: String,
-    pub xrpeur_fee: String,
-    pub xrpusd_fee: String,
+pub struct CryptoTransaction {
+    pub currency: String,
+    #[serde(deserialize_with = "decimal_string")]
+    pub amount: String,
+    /// Unix timestamp
+    #[serde(deserialize_with = "string_or_number")]
+    pub datetime: String,
+    #[serde(default)]
+    pub txid: String,
+    #[serde(rename = "destinationAddress", alias = "destination_address", default)]
+    pub destination_address: String,
+}
+
+/// Reply of `crypto_transactions`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CryptoTransactions {
+    #[serde(default)]
+    pub deposits: Vec<CryptoTransaction>,
+    #[serde(default)]
+    pub withdrawals: Vec<CryptoTransaction>,
+    /// Only filled with `include_ious`
+    #[serde(default)]
+    pub ripple_iou_transactions: Vec<CryptoTransaction>,
+}
+
+/// Which list of the `crypto_transactions` reply a transaction came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CryptoTransactionKind {
+    Deposit,
+    Withdrawal,
+    RippleIou,
+}
+
+impl CryptoTransactions {
+    /// All transactions tagged with their kind, newest first
+    pub fn into_tagged(self) -> Vec<(CryptoTransactionKind, CryptoTransaction)> {
+        let mut tagged: Vec<(CryptoTransactionKind, CryptoTransaction)> = self
+            .deposits
+            .into_iter()
+            .map(|t| (CryptoTransactionKind::Deposit, t))
+            .chain(self.withdrawals.into_iter().map(|t| (CryptoTransactionKind::Withdrawal, t)))
+            .chain(self.ripple_iou_transactions.into_iter().map(|t| (CryptoTransactionKind::RippleIou, t)))
+            .collect();
+        // Stable, so equal timestamps keep the reply's order
+        tagged.sort_by_key(|(_, t)| std::cmp::Reverse(t.datetime.parse::<i64>().unwrap_or_default()));
+        tagged
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -131,27 +1106,177 @@ pub struct V1Error {
     pub error: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Event stream health counters, see `BitstampEventStream::stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamStats {
+    /// WebSocket frames of any kind
+    pub messages_received: u64,
+    /// Frames that couldn't be decoded into an `Event`, a rising rate suggests an API change
+    pub parse_failures: u64,
+    pub pings: u64,
+    pub pongs: u64,
+    pub reconnects: u64,
+    /// Events dropped as already seen, see `BitstampEventStream::set_dedup`
+    pub duplicates: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConnectionEventKind {
+    Connected,
+    Disconnected,
+    Reconnected,
+}
+
+/// One entry of `BitstampEventStream::connection_history`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionEvent {
+    pub kind: ConnectionEventKind,
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// Why the connection was lost, only for `Disconnected`
+    pub reason: Option<String>,
+}
+
+/// Outcome of `BitstampEventStream::subscribe_all`
+#[derive(Debug, Default)]
+pub struct SubscribeReport {
+    /// Confirmed channels, in the order the confirmations arrived
+    pub subscribed: Vec<EventChannel>,
+    /// Rejected channels and why, including those without a reply in time
+    pub failed: Vec<(EventChannel, Error)>,
+}
+
+impl SubscribeReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct Event {
     pub event: EventEvent,
     pub channel: EventChannel,
     pub data: EventData,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// `data` is decoded as the variant the event and channel call for, so a payload that
+/// doesn't fit fails with the missing or mistyped field, the channel and the original JSON
+/// instead of quietly matching another variant. Control events with a payload no variant
+/// describes are kept as `EventData::Raw`.
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawEvent {
+            event: EventEvent,
+            channel: EventChannel,
+            #[serde(default)]
+            data: serde_json::Value,
+        }
+
+        let raw = RawEvent::deserialize(deserializer)?;
+        let variant = match (&raw.event, &raw.channel) {
+            (EventEvent::Trade, EventChannel::LiveTrades(_)) => Some("Trade"),
+            (EventEvent::Trade, EventChannel::MyTrades(..)) => Some("MyTrade"),
+            (EventEvent::OrderCreated | EventEvent::OrderChanged | EventEvent::OrderDeleted, EventChannel::LiveOrders(_) | EventChannel::MyOrders(..)) => Some("Orders"),
+            (EventEvent::Data, EventChannel::OrderBook(_) | EventChannel::DetailOrderBook(_) | EventChannel::DiffOrderBook(_)) => Some("OrderBook"),
+            _ => None,
+        };
+        let data = match variant {
+            Some(variant) => {
+                let tagged = serde_json::json!({ variant: raw.data });
+                EventDataDef::deserialize(tagged).map_err(|e| {
+                    de::Error::custom(format!("invalid {:?} data on {:?}: {}. Original JSON: {}", raw.event, raw.channel, e, raw.data))
+                })?
+            }
+            None => match &raw.data {
+                serde_json::Value::Null => EventData::Empty {},
+                serde_json::Value::Object(fields) if fields.is_empty() => EventData::Empty {},
+                _ => match EventData::deserialize(&raw.data) {
+                    Ok(EventData::Empty {}) | Err(_) => EventData::Raw(raw.data),
+                    Ok(data) => data,
+                },
+            },
+        };
+        Ok(Event { event: raw.event, channel: raw.channel, data })
+    }
+}
+
+impl Event {
+    /// What happened to the order of a `LiveOrders`/`MyOrders` event, `None` for other events
+    pub fn order_action(&self) -> Option<OrderAction> {
+        match self.event {
+            EventEvent::OrderCreated => Some(OrderAction::Created),
+            EventEvent::OrderChanged => Some(OrderAction::Changed),
+            EventEvent::OrderDeleted => Some(OrderAction::Deleted),
+            _ => None,
+        }
+    }
+
+    /// Check that `data` is the kind the channel sends, e.g. that a `trade` event didn't
+    /// arrive on an order book channel.
+    pub fn check_consistency(&self) -> Result<(), String> {
+        let consistent = match (&self.event, &self.data) {
+            (EventEvent::Trade, EventData::Trade { .. }) => matches!(self.channel, EventChannel::LiveTrades(_)),
+            (EventEvent::Trade, EventData::MyTrade { .. }) => matches!(self.channel, EventChannel::MyTrades(..)),
+            (EventEvent::OrderCreated | EventEvent::OrderChanged | EventEvent::OrderDeleted, EventData::Orders { .. }) => {
+                matches!(self.channel, EventChannel::LiveOrders(_) | EventChannel::MyOrders(..))
+            }
+            (EventEvent::Data, EventData::OrderBook { .. }) => matches!(
+                self.channel,
+                EventChannel::OrderBook(_) | EventChannel::DetailOrderBook(_) | EventChannel::DiffOrderBook(_)
+            ),
+            (EventEvent::Trade | EventEvent::OrderCreated | EventEvent::OrderChanged | EventEvent::OrderDeleted | EventEvent::Data, _) => false,
+            // Control events carry no market data
+            (_, EventData::Empty {} | EventData::Error { .. } | EventData::Raw(_)) => true,
+            _ => false,
+        };
+        if consistent {
+            Ok(())
+        } else {
+            Err(format!("{:?} data doesn't match {:?} on {:?}", self.data, self.event, self.channel))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum EventEvent {
     #[serde(rename = "bts:subscribe")]
     BtsSubscribe,
     #[serde(rename = "bts:unsubscribe")]
     BtsUnsubscribe,
+    /// Generated locally (never sent by Bitstamp) after the stream reconnected and
+    /// resubscribed the channel, anything sent in between was missed
+    #[serde(rename = "bts:reconnected")]
+    Reconnected,
+    #[serde(rename = "bts:subscription_succeeded")]
+    SubscriptionSucceeded,
+    #[serde(rename = "bts:subscription_failed")]
+    SubscriptionFailed,
+    #[serde(rename = "bts:error")]
+    BtsError,
     Trade,
     OrderCreated,
     OrderChanged,
     OrderDeleted,
+    /// A full book on `OrderBook`/`DetailOrderBook` or a change set on `DiffOrderBook`, the
+    /// payload is always `EventData::OrderBook`
     Data,
 }
 
+/// See `Event::order_action`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderAction {
+    /// Placed, the payload has the full order
+    Created,
+    /// Partially filled or otherwise modified, the payload has the remaining amount
+    Changed,
+    /// Filled or cancelled, the payload is the order as it last was
+    Deleted,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutEvent {
     pub event: EventEvent,
@@ -161,6 +1286,9 @@ pub struct OutEvent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutEventData {
     pub channel: EventChannel,
+    /// WebSocket token, only for private channels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -172,21 +1300,31 @@ pub enum EventData {
         timestamp: String,
         microtimestamp: String,
         id: i64,
+        /// Lossy, use `EventData::amount`
+        #[serde(deserialize_with = "number")]
         amount: f64,
         sell_order_id: i64,
         price_str: String,
         #[serde(rename = "type")]
         type_field: i64,
+        /// Lossy, use `EventData::price`
+        #[serde(deserialize_with = "number")]
         price: f64,
     },
+    /// An order on the `LiveOrders`/`MyOrders` channels. The same payload is sent whether the
+    /// order was created, changed or deleted, see `Event::order_action` for which.
     Orders {
         id: i64,
         id_str: String,
         order_type: i64,
         datetime: String,
         microtimestamp: String,
+        /// Lossy, use `EventData::amount`
+        #[serde(deserialize_with = "number")]
         amount: f64,
         amount_str: String,
+        /// Lossy, use `EventData::price`
+        #[serde(deserialize_with = "number")]
         price: f64,
         price_str: String,
     },
@@ -196,16 +1334,289 @@ pub enum EventData {
         bids: Vec<Vec<String>>,
         asks: Vec<Vec<String>>,
     },
+    /// Fill of one of the account's own orders, from `MyTrades`
+    MyTrade {
+        id: i64,
+        order_id: i64,
+        #[serde(default)]
+        client_order_id: Option<String>,
+        amount: String,
+        price: String,
+        fee: String,
+        side: String,
+        microtimestamp: String,
+    },
+    /// Payload of `bts:error` events
+    Error {
+        #[serde(default)]
+        code: Option<serde_json::Value>,
+        message: String,
+    },
     Empty {},
+    /// Payload of a control event that none of the other variants describe
+    Raw(serde_json::Value),
+}
+
+/// The exact string fields decide equality, the lossy `amount`/`price` floats next to
+/// `amount_str`/`price_str` are ignored
+impl PartialEq for EventData {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                EventData::Trade { buy_order_id, amount_str, timestamp, microtimestamp, id, sell_order_id, price_str, type_field, .. },
+                EventData::Trade {
+                    buy_order_id: other_buy_order_id,
+                    amount_str: other_amount_str,
+                    timestamp: other_timestamp,
+                    microtimestamp: other_microtimestamp,
+                    id: other_id,
+                    sell_order_id: other_sell_order_id,
+                    price_str: other_price_str,
+                    type_field: other_type_field,
+                    ..
+                },
+            ) => {
+                (buy_order_id, amount_str, timestamp, microtimestamp, id, sell_order_id, price_str, type_field)
+                    == (other_buy_order_id, other_amount_str, other_timestamp, other_microtimestamp, other_id, other_sell_order_id, other_price_str, other_type_field)
+            }
+            (
+                EventData::Orders { id, id_str, order_type, datetime, microtimestamp, amount_str, price_str, .. },
+                EventData::Orders {
+                    id: other_id,
+                    id_str: other_id_str,
+                    order_type: other_order_type,
+                    datetime: other_datetime,
+                    microtimestamp: other_microtimestamp,
+                    amount_str: other_amount_str,
+                    price_str: other_price_str,
+                    ..
+                },
+            ) => {
+                (id, id_str, order_type, datetime, microtimestamp, amount_str, price_str)
+                    == (other_id, other_id_str, other_order_type, other_datetime, other_microtimestamp, other_amount_str, other_price_str)
+            }
+            (
+                EventData::OrderBook { timestamp, microtimestamp, bids, asks },
+                EventData::OrderBook { timestamp: other_timestamp, microtimestamp: other_microtimestamp, bids: other_bids, asks: other_asks },
+            ) => (timestamp, microtimestamp, bids, asks) == (other_timestamp, other_microtimestamp, other_bids, other_asks),
+            (
+                EventData::MyTrade { id, order_id, client_order_id, amount, price, fee, side, microtimestamp },
+                EventData::MyTrade {
+                    id: other_id,
+                    order_id: other_order_id,
+                    client_order_id: other_client_order_id,
+                    amount: other_amount,
+                    price: other_price,
+                    fee: other_fee,
+                    side: other_side,
+                    microtimestamp: other_microtimestamp,
+                },
+            ) => {
+                (id, order_id, client_order_id, amount, price, fee, side, microtimestamp)
+                    == (other_id, other_order_id, other_client_order_id, other_amount, other_price, other_fee, other_side, other_microtimestamp)
+            }
+            (EventData::Error { code, message }, EventData::Error { code: other_code, message: other_message }) => {
+                (code, message) == (other_code, other_message)
+            }
+            (EventData::Empty {}, EventData::Empty {}) => true,
+            (EventData::Raw(value), EventData::Raw(other_value)) => value == other_value,
+            _ => false,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+impl Eq for EventData {}
+
+/// Consistent with `PartialEq`, the floats aren't hashed
+impl std::hash::Hash for EventData {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            EventData::Trade { buy_order_id, amount_str, timestamp, microtimestamp, id, sell_order_id, price_str, type_field, .. } => {
+                (buy_order_id, amount_str, timestamp, microtimestamp, id, sell_order_id, price_str, type_field).hash(state)
+            }
+            EventData::Orders { id, id_str, order_type, datetime, microtimestamp, amount_str, price_str, .. } => {
+                (id, id_str, order_type, datetime, microtimestamp, amount_str, price_str).hash(state)
+            }
+            EventData::OrderBook { timestamp, microtimestamp, bids, asks } => (timestamp, microtimestamp, bids, asks).hash(state),
+            EventData::MyTrade { id, order_id, client_order_id, amount, price, fee, side, microtimestamp } => {
+                (id, order_id, client_order_id, amount, price, fee, side, microtimestamp).hash(state)
+            }
+            // `Value` has no `Hash`, hash its JSON text instead
+            EventData::Error { code, message } => (code.as_ref().map(|code| code.to_string()), message).hash(state),
+            EventData::Empty {} => {}
+            EventData::Raw(value) => value.to_string().hash(state),
+        }
+    }
+}
+
+/// Externally tagged twin of `EventData`, for decoding a payload as one given variant
+#[derive(Deserialize)]
+#[serde(remote = "EventData")]
+enum EventDataDef {
+    Trade {
+        buy_order_id: i64,
+        amount_str: String,
+        timestamp: String,
+        microtimestamp: String,
+        id: i64,
+        #[serde(deserialize_with = "number")]
+        amount: f64,
+        sell_order_id: i64,
+        price_str: String,
+        #[serde(rename = "type")]
+        type_field: i64,
+        #[serde(deserialize_with = "number")]
+        price: f64,
+    },
+    Orders {
+        id: i64,
+        id_str: String,
+        order_type: i64,
+        datetime: String,
+        microtimestamp: String,
+        #[serde(deserialize_with = "number")]
+        amount: f64,
+        amount_str: String,
+        #[serde(deserialize_with = "number")]
+        price: f64,
+        price_str: String,
+    },
+    OrderBook {
+        timestamp: String,
+        microtimestamp: String,
+        bids: Vec<Vec<String>>,
+        asks: Vec<Vec<String>>,
+    },
+    MyTrade {
+        id: i64,
+        order_id: i64,
+        #[serde(default)]
+        client_order_id: Option<String>,
+        amount: String,
+        price: String,
+        fee: String,
+        side: String,
+        microtimestamp: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum EventChannel {
     LiveTrades(CurrencyPairs),
     LiveOrders(CurrencyPairs),
     OrderBook(CurrencyPairs),
     DetailOrderBook(CurrencyPairs),
     DiffOrderBook(CurrencyPairs),
+    /// Private, the account's own orders. Needs the `user_id` and a token from `get_websocket_token`.
+    MyOrders(CurrencyPairs, i64),
+    /// Private, the account's own trades. Needs the `user_id` and a token from `get_websocket_token`.
+    MyTrades(CurrencyPairs, i64),
+}
+
+impl EventData {
+    /// The book carried by an `OrderBook` event (any of the order book channels), in the
+    /// same shape as a REST snapshot
+    pub fn to_order_book(&self) -> Option<OrderBook> {
+        match self {
+            EventData::OrderBook { timestamp, microtimestamp, bids, asks } => Some(OrderBook {
+                timestamp: timestamp.clone(),
+                microtimestamp: microtimestamp.clone(),
+                bids: bids.clone(),
+                asks: asks.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Exact amount of a trade or order, parsed from the string field. `None` for other
+    /// payloads or if it isn't a number.
+    pub fn amount(&self) -> Option<Decimal> {
+        match self {
+            EventData::Trade { amount_str, .. } | EventData::Orders { amount_str, .. } => Decimal::from_str(amount_str).ok(),
+            EventData::MyTrade { amount, .. } => Decimal::from_str(amount).ok(),
+            _ => None,
+        }
+    }
+
+    /// Exact price of a trade or order, parsed from the string field. `None` for other
+    /// payloads or if it isn't a number.
+    pub fn price(&self) -> Option<Decimal> {
+        match self {
+            EventData::Trade { price_str, .. } | EventData::Orders { price_str, .. } => Decimal::from_str(price_str).ok(),
+            EventData::MyTrade { price, .. } => Decimal::from_str(price).ok(),
+            _ => None,
+        }
+    }
+
+    /// The float `amount` Bitstamp sends next to `amount_str`, which can't represent every
+    /// amount exactly. Prefer `amount`.
+    pub fn amount_f64_lossy(&self) -> Option<f64> {
+        match self {
+            EventData::Trade { amount, .. } | EventData::Orders { amount, .. } => Some(*amount),
+            _ => None,
+        }
+    }
+
+    /// The float `price` Bitstamp sends next to `price_str`, which can't represent every
+    /// price exactly. Prefer `price`.
+    pub fn price_f64_lossy(&self) -> Option<f64> {
+        match self {
+            EventData::Trade { price, .. } | EventData::Orders { price, .. } => Some(*price),
+            _ => None,
+        }
+    }
+
+    /// `microtimestamp` in microseconds since the epoch, `None` for payloads without one
+    pub fn micro_secs(&self) -> Option<u64> {
+        match self {
+            EventData::Trade { microtimestamp, .. }
+            | EventData::Orders { microtimestamp, .. }
+            | EventData::OrderBook { microtimestamp, .. }
+            | EventData::MyTrade { microtimestamp, .. } => microtimestamp.parse().ok(),
+            EventData::Error { .. } | EventData::Empty {} | EventData::Raw(_) => None,
+        }
+    }
+
+    /// Event time in whole seconds since the epoch, from `timestamp` (`datetime` for orders)
+    /// or derived from `microtimestamp` where the payload has no seconds field
+    pub fn timestamp_secs(&self) -> Option<i64> {
+        match self {
+            EventData::Trade { timestamp, .. } | EventData::OrderBook { timestamp, .. } => timestamp.parse().ok(),
+            EventData::Orders { datetime, .. } => datetime.parse().ok(),
+            EventData::MyTrade { .. } => self.micro_secs().map(|micros| (micros / 1_000_000) as i64),
+            EventData::Error { .. } | EventData::Empty {} | EventData::Raw(_) => None,
+        }
+    }
+
+    /// Event time, at microsecond precision when the payload has a `microtimestamp`
+    pub fn instant(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        use chrono::TimeZone;
+        match self.micro_secs() {
+            Some(micros) => chrono::Utc
+                .timestamp_opt((micros / 1_000_000) as i64, (micros % 1_000_000) as u32 * 1000)
+                .single(),
+            None => chrono::Utc.timestamp_opt(self.timestamp_secs()?, 0).single(),
+        }
+    }
+}
+
+impl TryFrom<EventData> for OrderBook {
+    type Error = String;
+
+    fn try_from(data: EventData) -> Result<Self, Self::Error> {
+        match data {
+            EventData::OrderBook { timestamp, microtimestamp, bids, asks } => Ok(OrderBook { timestamp, microtimestamp, bids, asks }),
+            other => Err(format!("not order book data: {:?}", other)),
+        }
+    }
+}
+
+impl EventChannel {
+    /// Private channels need a WebSocket token to subscribe
+    pub fn is_private(&self) -> bool {
+        matches!(self, EventChannel::MyOrders(..) | EventChannel::MyTrades(..))
+    }
 }
 
 impl Serialize for EventChannel {
@@ -215,12 +1626,51 @@ impl Serialize for EventChannel {
     {
         match self {
             EventChannel::LiveTrades(pair) => {
-                serializer.serialize_str(format!("live_trades_{:?}", pair).to_lowercase().as_str())
+                serializer.serialize_str(format!("live_trades_{}", pair).as_str())
+            }
+            EventChannel::LiveOrders(pair) => { serializer.serialize_str(format!("live_orders_{}", pair).as_str()) }
+            EventChannel::OrderBook(pair) => { serializer.serialize_str(format!("order_book_{}", pair).as_str()) }
+            EventChannel::DetailOrderBook(pair) => { serializer.serialize_str(format!("detail_order_book_{}", pair).as_str()) }
+            EventChannel::DiffOrderBook(pair) => { serializer.serialize_str(format!("diff_order_book_{}", pair).as_str()) }
+            EventChannel::MyOrders(pair, user_id) => { serializer.serialize_str(format!("private-my_orders_{}-{}", pair, user_id).as_str()) }
+            EventChannel::MyTrades(pair, user_id) => { serializer.serialize_str(format!("private-my_trades_{}-{}", pair, user_id).as_str()) }
+        }
+    }
+}
+
+impl TryFrom<&str> for EventChannel {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (s, user_id) = match s.strip_prefix("private-") {
+            Some(private) => {
+                let (name, user_id) = private.rsplit_once('-').ok_or_else(|| "Missing user id".to_string())?;
+                (name, Some(user_id.parse::<i64>().map_err(|_| format!("Invalid user id: {}", user_id))?))
+            }
+            None => (s, None),
+        };
+        let mut parts: Vec<&str> = s.split('_').collect();
+        let pair: CurrencyPairs = match parts.last() {
+            None => { return Err("Failed to parse CurrencyPairs".to_string()); }
+            Some(p) => {
+                match CurrencyPairs::from_str(p) {
+                    Ok(pa) => { pa }
+                    Err(_) => { return Err(format!("Unknown currency pair: {}", p)); }
+                }
+            }
+        };
+        parts.truncate(parts.len().saturating_sub(1));
+        match (parts.join("_").as_str(), user_id) {
+            ("live_trades", None) => { Ok(EventChannel::LiveTrades(pair)) }
+            ("live_orders", None) => { Ok(EventChannel::LiveOrders(pair)) }
+            ("order_book", None) => { Ok(EventChannel::OrderBook(pair)) }
+            ("detail_order_book", None) => { Ok(EventChannel::DetailOrderBook(pair)) }
+            ("diff_order_book", None) => { Ok(EventChannel::DiffOrderBook(pair)) }
+            ("my_orders", Some(user_id)) => { Ok(EventChannel::MyOrders(pair, user_id)) }
+            ("my_trades", Some(user_id)) => { Ok(EventChannel::MyTrades(pair, user_id)) }
+            _ => {
+                Err("Unknown channel".to_string())
             }
-            EventChannel::LiveOrders(pair) => { serializer.serialize_str(format!("live_orders_{:?}", pair).to_lowercase().as_str()) }
-            EventChannel::OrderBook(pair) => { serializer.serialize_str(format!("order_book_{:?}", pair).to_lowercase().as_str()) }
-            EventChannel::DetailOrderBook(pair) => { serializer.serialize_str(format!("detail_order_book_{:?}", pair).to_lowercase().as_str()) }
-            EventChannel::DiffOrderBook(pair) => { serializer.serialize_str(format!("diff_order_book_{:?}", pair).to_lowercase().as_str()) }
         }
     }
 }
@@ -239,27 +1689,7 @@ impl<'de> Deserialize<'de> for EventChannel {
             }
 
             fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
-                let mut parts: Vec<&str> = s.split('_').collect();
-                let pair: CurrencyPairs = match parts.last() {
-                    None => { return Err(de::Error::custom("Failed to parse CurrencyPairs")); }
-                    Some(p) => {
-                        match CurrencyPairs::from_str(p) {
-                            Ok(pa) => { pa }
-                            Err(e) => { return Err(de::Error::custom(format!("Unknown currency pair: {}", p))); }
-                        }
-                    }
-                };
-                parts.truncate(parts.len().saturating_sub(1));
-                match parts.join("_").as_str() {
-                    "live_trades" => { Ok(EventChannel::LiveTrades(pair)) }
-                    "live_orders" => { Ok(EventChannel::LiveOrders(pair)) }
-                    "order_book" => { Ok(EventChannel::OrderBook(pair)) }
-                    "detail_order_book" => { Ok(EventChannel::DetailOrderBook(pair)) }
-                    "diff_order_book" => { Ok(EventChannel::DiffOrderBook(pair)) }
-                    _ => {
-                        Err(de::Error::custom("Unknown channel"))
-                    }
-                }
+                EventChannel::try_from(s).map_err(de::Error::custom)
             }
 
 
@@ -272,8 +1702,7 @@ impl<'de> Deserialize<'de> for EventChannel {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CurrencyPairs {
     Btcusd,
     Btceur,
@@ -292,6 +1721,106 @@ pub enum CurrencyPairs {
     Bchbtc,
 }
 
+impl CurrencyPairs {
+    pub const ALL: [CurrencyPairs; 15] = [
+        CurrencyPairs::Btcusd,
+        CurrencyPairs::Btceur,
+        CurrencyPairs::Eurusd,
+        CurrencyPairs::Xrpusd,
+        CurrencyPairs::Xrpeur,
+        CurrencyPairs::Xrpbtc,
+        CurrencyPairs::Ltcusd,
+        CurrencyPairs::Ltceur,
+        CurrencyPairs::Ltcbtc,
+        CurrencyPairs::Ethusd,
+        CurrencyPairs::Etheur,
+        CurrencyPairs::Ethbtc,
+        CurrencyPairs::Bchusd,
+        CurrencyPairs::Bcheur,
+        CurrencyPairs::Bchbtc,
+    ];
+
+    /// Exchange symbol of the pair, used in REST paths and channel names
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CurrencyPairs::Btcusd => "btcusd",
+            CurrencyPairs::Btceur => "btceur",
+            CurrencyPairs::Eurusd => "eurusd",
+            CurrencyPairs::Xrpusd => "xrpusd",
+            CurrencyPairs::Xrpeur => "xrpeur",
+            CurrencyPairs::Xrpbtc => "xrpbtc",
+            CurrencyPairs::Ltcusd => "ltcusd",
+            CurrencyPairs::Ltceur => "ltceur",
+            CurrencyPairs::Ltcbtc => "ltcbtc",
+            CurrencyPairs::Ethusd => "ethusd",
+            CurrencyPairs::Etheur => "etheur",
+            CurrencyPairs::Ethbtc => "ethbtc",
+            CurrencyPairs::Bchusd => "bchusd",
+            CurrencyPairs::Bcheur => "bcheur",
+            CurrencyPairs::Bchbtc => "bchbtc",
+        }
+    }
+
+    /// REST path component of the pair, the same as `PairInfo::url_symbol`
+    pub fn url_symbol(&self) -> String {
+        self.as_str().to_string()
+    }
+
+    /// The traded currency, `"btc"` for btcusd
+    pub fn base(&self) -> &'static str {
+        &self.as_str()[..3]
+    }
+
+    /// The currency prices are in, `"usd"` for btcusd
+    pub fn quote(&self) -> &'static str {
+        &self.as_str()[3..]
+    }
+
+    /// Every pair quoted in `quote` (case insensitive), e.g. all USD markets
+    pub fn by_quote(quote: &str) -> Vec<CurrencyPairs> {
+        CurrencyPairs::ALL.iter().filter(|pair| pair.quote().eq_ignore_ascii_case(quote)).copied().collect()
+    }
+}
+
+impl std::fmt::Display for CurrencyPairs {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The url symbol, so a pair can be passed wherever REST methods take a `currency_pair`
+impl AsRef<str> for CurrencyPairs {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<CurrencyPairs> for String {
+    fn from(pair: CurrencyPairs) -> String {
+        pair.as_str().to_string()
+    }
+}
+
+/// Serialized as the exchange symbol, the same as `Display`
+impl Serialize for CurrencyPairs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CurrencyPairs {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        CurrencyPairs::from_str(&s).map_err(|_| de::Error::custom(format!("unknown currency pair: {}", s)))
+    }
+}
+
 impl FromStr for CurrencyPairs {
     type Err = ();
 
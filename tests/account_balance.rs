@@ -0,0 +1,60 @@
+mod common;
+
+use std::str::FromStr;
+
+use bitstamp::types::{AccountBalance, CurrencyPairs, Decimal, PairTicker, Ticker};
+
+#[test]
+fn fee_lookup_covers_every_pair() {
+    // A distinct fee per pair, so a field mixup shows
+    let fees: serde_json::Map<String, serde_json::Value> = CurrencyPairs::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, pair)| (format!("{}_fee", pair), format!("0.{}", i + 10).into()))
+        .collect();
+    let balance: AccountBalance = serde_json::from_value(serde_json::Value::Object(fees)).unwrap();
+
+    for (i, pair) in CurrencyPairs::ALL.iter().enumerate() {
+        let expected = format!("0.{}", i + 10);
+        assert_eq!(balance.fee(pair), Some(common::number(&expected).as_str()), "{}", pair);
+        assert_eq!(balance.fee_decimal(pair), Some(Decimal::from_str(&expected).unwrap()));
+    }
+    assert_eq!(AccountBalance::default().fee(&CurrencyPairs::Btcusd), None);
+}
+
+fn ticker(pair: &str, last: &str) -> PairTicker {
+    PairTicker { pair: pair.to_string(), ticker: Ticker { last: last.to_string(), ..Default::default() } }
+}
+
+#[test]
+fn total_in_converts_through_other_pairs() {
+    let balance = AccountBalance {
+        btc_balance: "0.5".to_string(),
+        eth_balance: "2".to_string(),
+        usd_balance: "100".to_string(),
+        eur_balance: "0.00".to_string(),
+        ..Default::default()
+    };
+    let tickers = [ticker("BTC/USD", "40000.00"), ticker("ETH/EUR", "2000.00"), ticker("EUR/USD", "1.25")];
+    let d = |s: &str| Decimal::from_str(s).unwrap();
+
+    assert_eq!(balance.total_in("usd", &tickers).unwrap(), d("25100"));
+    assert_eq!(balance.total_in("EUR", &tickers).unwrap(), d("20080"));
+
+    let with_xrp = AccountBalance { xrp_balance: "10".to_string(), ..balance };
+    let err = with_xrp.total_in("usd", &tickers).unwrap_err();
+    assert!(err.to_string().contains("xrp"));
+}
+
+#[test]
+fn unmapped_fields_are_kept() {
+    let json = r#"{"btc_balance": "0.5", "link_balance": "12.00000000", "margin_level": 1.75, "liquidation_price": null}"#;
+    let balance: AccountBalance = serde_json::from_str(json).unwrap();
+    assert_eq!(balance.btc_balance, "0.5");
+    assert_eq!(balance.extra.len(), 2);
+    assert_eq!(balance.extra["link_balance"], "12.00000000");
+    assert_eq!(balance.extra["margin_level"], "1.75");
+
+    let round_trip: AccountBalance = serde_json::from_value(serde_json::to_value(&balance).unwrap()).unwrap();
+    assert_eq!(round_trip, balance);
+}
@@ -0,0 +1,111 @@
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+
+use bitstamp::transport::{Transport, TransportFuture};
+use bitstamp::Bitstamp;
+use hyper::{Body, HeaderMap, Request, Response, StatusCode};
+
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e))
+}
+
+/// How a numeric string from a fixture reads after deserializing, `"0.50000000"` is `"0.5"`
+/// with the normalize-decimals feature
+pub fn number(raw: &str) -> String {
+    if cfg!(feature = "normalize-decimals") {
+        raw.parse::<bitstamp::types::Decimal>().unwrap().normalize().to_string()
+    } else {
+        raw.to_string()
+    }
+}
+
+/// Path, status, body, headers and whether it's only used once
+type Route = (String, StatusCode, String, Vec<(String, String)>, bool);
+
+/// Serves canned responses keyed on the request path and records every request it sees.
+#[derive(Clone, Default)]
+pub struct FixtureTransport {
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    headers: Arc<Mutex<Vec<HeaderMap>>>,
+    bodies: Arc<Mutex<Vec<String>>>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> Self {
+        FixtureTransport::default()
+    }
+
+    /// Answer requests whose path ends with `path` with the named fixture
+    pub fn route(self, path: &str, status: StatusCode, fixture_name: &str) -> Self {
+        self.route_body(path, status, fixture(fixture_name))
+    }
+
+    pub fn route_body(self, path: &str, status: StatusCode, body: String) -> Self {
+        self.route_with_headers(path, status, body, &[])
+    }
+
+    pub fn route_with_headers(self, path: &str, status: StatusCode, body: String, headers: &[(&str, &str)]) -> Self {
+        let headers = headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        self.routes.lock().unwrap().push((path.to_string(), status, body, headers, false));
+        self
+    }
+
+    /// Answer the next request for `path` with `body`, later ones fall through to the routes
+    /// added after it
+    pub fn route_once(self, path: &str, status: StatusCode, body: String) -> Self {
+        self.routes.lock().unwrap().push((path.to_string(), status, body, Vec::new(), true));
+        self
+    }
+
+    /// Paths of all requests made so far
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Headers of all requests made so far, in the same order as `requests`
+    pub fn request_headers(&self) -> Vec<HeaderMap> {
+        self.headers.lock().unwrap().clone()
+    }
+
+    /// Bodies of all requests made so far, in the order they were read
+    pub fn request_bodies(&self) -> Vec<String> {
+        self.bodies.lock().unwrap().clone()
+    }
+
+    pub fn client(&self) -> Bitstamp {
+        Bitstamp::builder("secret".to_string(), "key".to_string())
+            .transport(self.clone())
+            .build()
+    }
+}
+
+impl Transport for FixtureTransport {
+    fn request(&self, request: Request<Body>) -> TransportFuture {
+        let path = request.uri().path_and_query().map(|p| p.to_string()).unwrap_or_default();
+        self.requests.lock().unwrap().push(path.clone());
+        self.headers.lock().unwrap().push(request.headers().clone());
+        let route = {
+            let mut routes = self.routes.lock().unwrap();
+            let found = routes.iter().position(|(p, _, _, _, _)| path.trim_end_matches('/').ends_with(p.trim_end_matches('/')));
+            found.map(|i| if routes[i].4 { routes.remove(i) } else { routes[i].clone() })
+        };
+        let bodies = self.bodies.clone();
+        Box::pin(async move {
+            let body = hyper::body::to_bytes(request.into_body()).await?;
+            bodies.lock().unwrap().push(String::from_utf8_lossy(&body).into_owned());
+            match route {
+                Some((_, status, body, headers, _)) => {
+                    let mut response = Response::builder().status(status);
+                    for (name, value) in headers {
+                        response = response.header(name.as_str(), value.as_str());
+                    }
+                    Ok(response.body(Body::from(body)).unwrap())
+                }
+                None => Ok(Response::builder().status(StatusCode::NOT_FOUND).body(Body::from(format!("no fixture for {}", path))).unwrap()),
+            }
+        })
+    }
+}
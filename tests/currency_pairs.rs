@@ -0,0 +1,51 @@
+mod common;
+
+use std::str::FromStr;
+
+use bitstamp::types::{CurrencyPairs, PairInfo};
+
+#[test]
+fn serialized_form_matches_display() {
+    for pair in CurrencyPairs::ALL.iter() {
+        let json = serde_json::to_string(pair).unwrap();
+        assert_eq!(json, format!("\"{}\"", pair));
+        assert_eq!(serde_json::from_str::<CurrencyPairs>(&json).unwrap(), *pair);
+        #[allow(clippy::unnecessary_to_owned)]
+        let parsed = CurrencyPairs::from_str(&pair.to_string());
+        assert_eq!(parsed, Ok(*pair));
+        assert_eq!(pair.as_ref(), pair.to_string());
+    }
+    assert!(serde_json::from_str::<CurrencyPairs>("\"dogeusd\"").is_err());
+}
+
+#[test]
+fn url_symbol_matches_pairs_info() {
+    let info: Vec<PairInfo> = serde_json::from_str(&common::fixture("trading_pairs_info.json")).unwrap();
+    let mut symbols: Vec<String> = info.into_iter().map(|pair| pair.url_symbol).collect();
+    symbols.sort();
+    let mut ours: Vec<String> = CurrencyPairs::ALL.iter().map(CurrencyPairs::url_symbol).collect();
+    ours.sort();
+    assert_eq!(ours, symbols);
+}
+
+#[test]
+fn string_conversion_matches_pairs_info() {
+    let info: Vec<PairInfo> = serde_json::from_str(&common::fixture("trading_pairs_info.json")).unwrap();
+    for pair in info {
+        let typed = CurrencyPairs::from_str(&pair.url_symbol).unwrap();
+        assert_eq!(String::from(typed), pair.url_symbol);
+        assert_eq!(typed.as_ref(), pair.url_symbol);
+    }
+}
+
+#[test]
+fn pairs_group_by_quote() {
+    assert_eq!((CurrencyPairs::Ethbtc.base(), CurrencyPairs::Ethbtc.quote()), ("eth", "btc"));
+    let usd = CurrencyPairs::by_quote("USD");
+    assert_eq!(
+        usd,
+        [CurrencyPairs::Btcusd, CurrencyPairs::Eurusd, CurrencyPairs::Xrpusd, CurrencyPairs::Ltcusd, CurrencyPairs::Ethusd, CurrencyPairs::Bchusd]
+    );
+    assert_eq!(CurrencyPairs::by_quote("eur").len(), 5);
+    assert!(CurrencyPairs::by_quote("gbp").is_empty());
+}
@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use bitstamp::types::{ConversionRate, Decimal, PairInfo, PriceLevel};
+
+#[test]
+fn scientific_notation_matches_plain() {
+    let scientific = Decimal::from_str("1e-8").unwrap();
+    let plain = Decimal::from_str("0.00000001").unwrap();
+    assert_eq!(scientific, plain);
+    assert_eq!(scientific.to_string(), "0.00000001");
+
+    assert_eq!(Decimal::from_str("1.5E+3").unwrap(), Decimal::from_str("1500").unwrap());
+    assert_eq!(Decimal::from_str("-2.5e-3").unwrap(), Decimal::from_str("-0.0025").unwrap());
+    assert!(Decimal::from_str("1e").is_err());
+    assert!(Decimal::from_str("e5").is_err());
+}
+
+#[test]
+fn scientific_notation_in_payloads() {
+    let level = PriceLevel::from_strings(&["43210.55".to_string(), "1e-8".to_string()]).unwrap();
+    assert_eq!(level.amount, Decimal::from_str("0.00000001").unwrap());
+
+    let parsed: Decimal = serde_json::from_str("\"5E-7\"").unwrap();
+    assert_eq!(parsed, Decimal::from_str("0.0000005").unwrap());
+    let parsed: Decimal = serde_json::from_str("1e-8").unwrap();
+    assert_eq!(parsed, Decimal::from_str("0.00000001").unwrap());
+}
+
+#[test]
+fn numeric_strings_are_normalized_with_the_feature() {
+    let json = r#"{"high": "44500.00", "last": "1.50000000", "timestamp": "1633024800", "bid": "1.4", "vwap": "1.45",
+        "volume": "1893.20744534", "low": "1.2", "ask": "1.6", "open": "1e-8"}"#;
+    let ticker: bitstamp::types::Ticker = serde_json::from_str(json).unwrap();
+    if cfg!(feature = "normalize-decimals") {
+        assert_eq!((ticker.high.as_str(), ticker.last.as_str(), ticker.open.as_str()), ("44500", "1.5", "0.00000001"));
+    } else {
+        assert_eq!((ticker.high.as_str(), ticker.last.as_str(), ticker.open.as_str()), ("44500.00", "1.50000000", "1e-8"));
+    }
+    // Lossless either way
+    assert_eq!(Decimal::from_str(&ticker.last).unwrap(), Decimal::from_str("1.5").unwrap());
+    assert_eq!(ticker.timestamp, "1633024800");
+}
+
+#[test]
+fn comparing_far_apart_scales_does_not_overflow() {
+    let tiny = Decimal::new(1, 28);
+    let huge = Decimal::new(i128::MAX, 0);
+    assert!(tiny < huge);
+    assert!(-huge < -tiny);
+    assert_ne!(tiny, huge);
+    assert_eq!(Decimal::new(100, 30), tiny);
+    assert_eq!(huge.max(Decimal::new(5, 28)), huge);
+
+    let mut levels = std::collections::BTreeMap::new();
+    levels.insert(huge, 1);
+    levels.insert(tiny, 2);
+    assert_eq!(levels.keys().next(), Some(&tiny));
+}
+
+#[test]
+fn checked_arithmetic_reports_overflow() {
+    let huge = Decimal::new(i128::MAX, 0);
+    assert_eq!(huge.checked_add(Decimal::ONE), None);
+    assert_eq!((-huge).checked_sub(Decimal::new(2, 0)), None);
+    assert_eq!(huge.checked_mul(Decimal::new(2, 0)), None);
+    assert_eq!(huge.checked_add(Decimal::new(1, 28)), None);
+
+    let a = Decimal::from_str("1.5").unwrap();
+    let b = Decimal::from_str("0.25").unwrap();
+    assert_eq!(a.checked_add(b), Some(Decimal::from_str("1.75").unwrap()));
+    assert_eq!(a.checked_sub(b), Some(Decimal::from_str("1.25").unwrap()));
+    assert_eq!(a.checked_mul(b), Some(Decimal::from_str("0.375").unwrap()));
+}
+
+#[test]
+fn scales_stay_within_range() {
+    assert_eq!(Decimal::new(15, 29), Decimal::new(2, 28));
+    assert_eq!(Decimal::new(1, 40).scale(), 28);
+    assert_eq!(Decimal::new(1, 40).trunc(2), Decimal::ZERO);
+    assert_eq!(Decimal::new(i128::MAX, 200).round_dp(2), Decimal::ZERO);
+
+    let tiny = Decimal::from_str("0.0000000000000000000000000001").unwrap();
+    let product = tiny.checked_mul(tiny).unwrap();
+    assert_eq!(product.scale(), 28);
+    assert_eq!(product.trunc(8), Decimal::ZERO);
+    let product = Decimal::from_str("0.0000000000000000000000000003").unwrap().checked_mul(Decimal::from_str("0.5").unwrap());
+    assert_eq!(product, Some(Decimal::new(2, 28)));
+
+    let info = PairInfo { base_decimals: 8, counter_decimals: 2, ..Default::default() };
+    assert_eq!(info.round_amount(product.unwrap()), Decimal::ZERO);
+    assert_eq!(info.round_price(Decimal::new(1, 40)), Decimal::ZERO);
+}
+
+#[test]
+fn conversion_spread_reports_overflow() {
+    let rate = ConversionRate { buy: "100000000000000000000000000000000000000".to_string(), sell: "0.1".to_string() };
+    assert!(rate.spread().unwrap_err().to_string().contains("overflows"));
+    let rate = ConversionRate { buy: "1.2".to_string(), sell: "x".to_string() };
+    assert!(rate.spread().unwrap_err().to_string().contains("invalid sell rate"));
+}
+
+#[test]
+fn out_of_range_exponents_are_rejected() {
+    assert!(Decimal::from_str("1e-4000000000").is_err());
+    assert!(Decimal::from_str("1e-2000000000").is_err());
+    assert!(Decimal::from_str("1e-29").is_err());
+    assert!(Decimal::from_str("0.00000000000000000000000000001").is_err());
+    assert!(Decimal::from_str("1e4000000000").is_err());
+    assert!(serde_json::from_str::<Decimal>("\"1e-4000000000\"").is_err());
+
+    assert_eq!(Decimal::from_str("1e-28").unwrap().scale(), 28);
+    // Trailing zeros don't count toward the limit
+    assert_eq!(Decimal::from_str("100e-30").unwrap(), Decimal::from_str("1e-28").unwrap());
+    assert_eq!(Decimal::from_str("0e-400").unwrap(), Decimal::ZERO);
+}
+
+#[test]
+fn book_order_and_balance_strings_are_normalized_with_the_feature() {
+    let book: bitstamp::types::OrderBook = serde_json::from_str(
+        r#"{"timestamp": "1633024800", "microtimestamp": "1633024800123456",
+        "bids": [["43205.10", "0.50000000"]], "asks": [["43210.00", "1e-8"]]}"#,
+    )
+    .unwrap();
+    let order: bitstamp::types::Order = serde_json::from_str(
+        r#"{"id": "1", "datetime": "2021-09-30 18:00:00", "type": "0", "price": "43000.00", "amount": "0.01000000"}"#,
+    )
+    .unwrap();
+    let balance: bitstamp::types::AccountBalance =
+        serde_json::from_str(r#"{"btc_balance": "1.50000000", "btcusd_fee": "0.500", "usd_available": "100.00"}"#).unwrap();
+
+    let normalized = cfg!(feature = "normalize-decimals");
+    let expect = |plain: &'static str, raw: &'static str| if normalized { plain } else { raw };
+    assert_eq!(book.bids[0], [expect("43205.1", "43205.10"), expect("0.5", "0.50000000")]);
+    assert_eq!(book.asks[0], [expect("43210", "43210.00"), expect("0.00000001", "1e-8")]);
+    assert_eq!((order.price.as_str(), order.amount.as_str()), (expect("43000", "43000.00"), expect("0.01", "0.01000000")));
+    assert_eq!(balance.btc_balance, expect("1.5", "1.50000000"));
+    assert_eq!(balance.btcusd_fee, expect("0.5", "0.500"));
+    assert_eq!(balance.usd_available, expect("100", "100.00"));
+    // Currencies missing from the reply stay empty
+    assert_eq!(balance.eth_balance, "");
+}
@@ -0,0 +1,47 @@
+use bitstamp::book::{DetailOrderBook, OrderChange};
+use bitstamp::types::EventData;
+
+fn event(microtimestamp: &str, bids: &[(&str, &str, &str)], asks: &[(&str, &str, &str)]) -> EventData {
+    let levels = |levels: &[(&str, &str, &str)]| {
+        levels.iter().map(|(p, a, id)| vec![p.to_string(), a.to_string(), id.to_string()]).collect()
+    };
+    EventData::OrderBook {
+        timestamp: microtimestamp[..10].to_string(),
+        microtimestamp: microtimestamp.to_string(),
+        bids: levels(bids),
+        asks: levels(asks),
+    }
+}
+
+#[test]
+fn tracks_orders_by_id() {
+    let mut book = DetailOrderBook::from_event(&event(
+        "1633024800123456",
+        &[("43205.12", "0.5", "101"), ("43205.12", "0.2", "102"), ("43200.00", "1", "103")],
+        &[("43212.40", "0.1", "201")],
+    ))
+    .unwrap();
+    assert_eq!(book.queue_position("102"), Some(1));
+    assert_eq!(book.order("201").unwrap().amount.to_string(), "0.1");
+
+    let mut changes = book
+        .update(&event(
+            "1633024800223456",
+            &[("43205.12", "0.1", "102"), ("43200.00", "1", "103")],
+            &[("43212.40", "0.1", "201"), ("43215.00", "3", "202")],
+        ))
+        .unwrap();
+    changes.sort_by_key(|c| match c {
+        OrderChange::Added(o) | OrderChange::Removed(o) | OrderChange::Modified { after: o, .. } => o.id.clone(),
+    });
+    assert_eq!(changes.len(), 3);
+    assert!(matches!(&changes[0], OrderChange::Removed(o) if o.id == "101"));
+    assert!(matches!(&changes[1], OrderChange::Modified { before, after } if before.amount.to_string() == "0.2" && after.amount.to_string() == "0.1"));
+    assert!(matches!(&changes[2], OrderChange::Added(o) if o.id == "202"));
+    assert_eq!(book.queue_position("102"), Some(0));
+    assert!(book.order("101").is_none());
+
+    // Stale events change nothing
+    assert!(book.update(&event("1633024800000000", &[], &[])).unwrap().is_empty());
+    assert_eq!(book.bids().len(), 2);
+}
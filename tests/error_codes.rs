@@ -0,0 +1,106 @@
+mod common;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitstamp::transport::{Transport, TransportFuture};
+use bitstamp::types::{LimitOrderRequest, TradeSide};
+use bitstamp::{Bitstamp, BitstampErrorCode};
+use common::{fixture, number, FixtureTransport};
+use hyper::{Body, Request, Response, StatusCode};
+
+/// Answers with the given replies in turn and records the nonces it sees
+#[derive(Clone, Default)]
+struct SequenceTransport {
+    replies: Arc<Mutex<Vec<(StatusCode, String)>>>,
+    nonces: Arc<Mutex<Vec<String>>>,
+}
+
+impl Transport for SequenceTransport {
+    fn request(&self, request: Request<Body>) -> TransportFuture {
+        let nonce = request.headers()["x-auth-nonce"].to_str().unwrap().to_string();
+        self.nonces.lock().unwrap().push(nonce);
+        let (status, body) = self.replies.lock().unwrap().remove(0);
+        Box::pin(async move { Ok(Response::builder().status(status).body(Body::from(body)).unwrap()) })
+    }
+}
+
+#[tokio::test]
+async fn auth_errors_are_classified() {
+    let bts = FixtureTransport::new()
+        .route("balance/", StatusCode::FORBIDDEN, "error_v2_reason_string.json")
+        .client();
+    let err = bts.get_balance().await.unwrap_err();
+    assert_eq!(err.code(), Some(BitstampErrorCode::InvalidSignature));
+    assert!(err.code().unwrap().is_fatal());
+    assert_eq!(BitstampErrorCode::from_code("API0001"), BitstampErrorCode::InvalidApiKey);
+}
+
+#[tokio::test]
+async fn reused_nonce_is_resent_with_a_fresh_one() {
+    let nonce_error = r#"{"status": "error", "reason": "Invalid nonce", "code": "API0004"}"#.to_string();
+    let transport = SequenceTransport::default();
+    transport.replies.lock().unwrap().extend(vec![
+        (StatusCode::FORBIDDEN, nonce_error),
+        (StatusCode::OK, fixture("balance.json")),
+    ]);
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .build();
+
+    let balance = bts.get_balance().await.unwrap();
+    assert_eq!(balance.btc_available, number("0.52000000"));
+    let nonces = transport.nonces.lock().unwrap().clone();
+    assert_eq!(nonces.len(), 2);
+    assert_eq!(nonces.iter().collect::<HashSet<_>>().len(), 2);
+}
+
+#[tokio::test]
+async fn nonce_resend_does_not_use_up_a_retry() {
+    let nonce_error = r#"{"status": "error", "reason": "Invalid nonce", "code": "API0004"}"#.to_string();
+    let transport = SequenceTransport::default();
+    transport.replies.lock().unwrap().extend(vec![
+        (StatusCode::FORBIDDEN, nonce_error),
+        (StatusCode::SERVICE_UNAVAILABLE, String::new()),
+        (StatusCode::OK, fixture("buy_order.json")),
+    ]);
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .max_retries(1)
+        .retry_backoff(Duration::from_millis(1))
+        .build();
+
+    let request = LimitOrderRequest {
+        amount: "0.01".to_string(),
+        price: "43000.00".to_string(),
+        client_order_id: Some("nonce-1".to_string()),
+        ..Default::default()
+    };
+    bts.limit_order(TradeSide::Buy, "btcusd", request).await.unwrap();
+    assert_eq!(transport.nonces.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn captured_replies_map_like_live_ones() {
+    use bitstamp::types::{parse_reply, parse_reply_with_status, ConversionRate};
+
+    let rate: ConversionRate = parse_reply(&fixture("eur_usd.json")).unwrap();
+    assert_eq!(rate.buy, "1.16010");
+
+    // A V2 error body is an error without a status too
+    let err = parse_reply::<serde_json::Value>(&fixture("error_v2_reason_string.json")).unwrap_err();
+    assert_eq!(err.code(), Some(BitstampErrorCode::InvalidSignature));
+    assert_eq!(err.status(), None);
+
+    let err = parse_reply_with_status::<serde_json::Value>(StatusCode::OK, &fixture("error_v2_reason_string.json")).unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::OK));
+
+    let err = parse_reply_with_status::<serde_json::Value>(StatusCode::NOT_FOUND, &fixture("error_v1.json")).unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+    assert!(err.to_string().contains("Invalid currency pair"));
+
+    let err = parse_reply_with_status::<serde_json::Value>(StatusCode::BAD_GATEWAY, "<html>").unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+    assert!(parse_reply::<ConversionRate>("[]").is_err());
+}
@@ -0,0 +1,42 @@
+use std::error::Error as _;
+
+use bitstamp::transport::{HttpsClient, Transport, TransportFuture};
+use bitstamp::Bitstamp;
+use hyper::{Body, Request, Uri};
+use hyper_tls::HttpsConnector;
+
+/// Sends every request to a local port nobody listens on
+struct UnreachableTransport(HttpsClient);
+
+impl Transport for UnreachableTransport {
+    fn request(&self, mut request: Request<Body>) -> TransportFuture {
+        *request.uri_mut() = Uri::from_static("http://127.0.0.1:1/");
+        Transport::request(&self.0, request)
+    }
+}
+
+#[tokio::test]
+async fn transport_error_keeps_hyper_source() {
+    let client = hyper::Client::builder().build::<_, Body>(HttpsConnector::new());
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(UnreachableTransport(client))
+        .build();
+
+    let err = bts.get_ticker("btcusd").await.unwrap_err();
+    assert!(err.is_transport());
+    let source = err.source().expect("transport error has a source");
+    let hyper_error = source.downcast_ref::<hyper::Error>().expect("source is a hyper::Error");
+    assert!(hyper_error.is_connect());
+}
+
+#[tokio::test]
+async fn connect_error_keeps_websocket_source() {
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url("ws://127.0.0.1:1")
+        .build();
+
+    let err = bts.event_stream().await.err().expect("nobody listens on the port");
+    let source = err.source().expect("connect error has a source");
+    let ws_error = source.downcast_ref::<tokio_tungstenite::tungstenite::Error>().expect("source is a tungstenite::Error");
+    assert!(matches!(ws_error, tokio_tungstenite::tungstenite::Error::Io(_)));
+}
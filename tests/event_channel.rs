@@ -0,0 +1,168 @@
+mod common;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use bitstamp::types::{CurrencyPairs, Decimal, Event, EventChannel, EventData, EventEvent, OrderAction};
+
+#[test]
+fn parses_every_channel_prefix() {
+    let cases = [
+        ("live_trades_btcusd", "live_trades_btcusd"),
+        ("live_orders_etheur", "live_orders_etheur"),
+        ("order_book_xrpbtc", "order_book_xrpbtc"),
+        ("detail_order_book_ltcusd", "detail_order_book_ltcusd"),
+        ("diff_order_book_bchbtc", "diff_order_book_bchbtc"),
+    ];
+    for (input, expected) in cases.iter() {
+        let channel = EventChannel::try_from(*input).unwrap();
+        assert_eq!(serde_json::to_string(&channel).unwrap(), format!("\"{}\"", expected));
+    }
+    match EventChannel::try_from("diff_order_book_btceur").unwrap() {
+        EventChannel::DiffOrderBook(CurrencyPairs::Btceur) => {}
+        other => panic!("unexpected channel {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_unknown_prefix() {
+    assert!(EventChannel::try_from("live_quotes_btcusd").is_err());
+    assert!(EventChannel::try_from("live_trades_dogeusd").is_err());
+}
+
+#[test]
+fn private_channels_carry_user_id() {
+    let channel = EventChannel::try_from("private-my_orders_btcusd-123").unwrap();
+    assert_eq!(channel, EventChannel::MyOrders(CurrencyPairs::Btcusd, 123));
+    assert!(channel.is_private());
+    assert_eq!(serde_json::to_string(&EventChannel::MyTrades(CurrencyPairs::Etheur, 7)).unwrap(), "\"private-my_trades_etheur-7\"");
+    assert!(EventChannel::try_from("my_orders_btcusd").is_err());
+    assert!(EventChannel::try_from("private-live_trades_btcusd-123").is_err());
+}
+
+#[test]
+fn event_data_must_match_channel() {
+    let trade = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+        "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+        "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+    let event: Event = serde_json::from_str(trade).unwrap();
+    assert!(event.check_consistency().is_ok());
+
+    // Missing fields name the field and the channel instead of falling through to another variant
+    let truncated = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"id": 2, "amount": 0.1}}"#;
+    let err = serde_json::from_str::<Event>(truncated).unwrap_err().to_string();
+    assert!(err.contains("missing field `buy_order_id`"), "{}", err);
+    assert!(err.contains("LiveTrades(Btcusd)") && err.contains(r#"{"amount":0.1,"id":2}"#), "{}", err);
+
+    let wrong_channel = trade.replace("live_trades_btcusd", "diff_order_book_btcusd");
+    let event: Event = serde_json::from_str(&wrong_channel).unwrap();
+    assert!(event.check_consistency().is_err());
+
+    let subscribed = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#;
+    let event: Event = serde_json::from_str(subscribed).unwrap();
+    assert!(event.check_consistency().is_ok());
+}
+
+#[test]
+fn event_time_units() {
+    let trade = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+        "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+        "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+    let event: Event = serde_json::from_str(trade).unwrap();
+    assert_eq!(event.data.timestamp_secs(), Some(1633024800));
+    assert_eq!(event.data.micro_secs(), Some(1633024800123456));
+    assert_eq!(event.data.instant().unwrap().to_rfc3339(), "2021-09-30T18:00:00.123456+00:00");
+
+    let subscribed = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#;
+    let event: Event = serde_json::from_str(subscribed).unwrap();
+    assert_eq!(event.data.instant(), None);
+}
+
+#[test]
+fn trade_amounts_are_exact() {
+    // 0.1 + 0.2 isn't 0.3 as a float
+    let trade = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.30000000",
+        "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.30000000000000004, "sell_order_id": 3,
+        "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+    let event: Event = serde_json::from_str(trade).unwrap();
+    assert_eq!(event.data.amount(), Some(Decimal::from_str("0.3").unwrap()));
+    assert_eq!(event.data.price(), Some(Decimal::from_str("43210.55").unwrap()));
+    assert_eq!(event.data.amount_f64_lossy(), Some(0.30000000000000004));
+
+    let subscribed = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#;
+    let event: Event = serde_json::from_str(subscribed).unwrap();
+    assert_eq!(event.data.amount(), None);
+}
+
+#[test]
+fn trade_floats_parse_from_the_wire_format() {
+    // As sent by Bitstamp, a whole price comes as an integer literal
+    let wire = common::fixture("ws_trade.json");
+    let event: Event = serde_json::from_str(&wire).unwrap();
+    assert_eq!(event.data.price_f64_lossy(), Some(43210.0));
+    assert_eq!(event.data.amount_f64_lossy(), Some(0.02));
+    assert_eq!(event.data.amount(), Some(Decimal::from_str("0.02").unwrap()));
+
+    let quoted = wire.replace(r#""amount": 0.02"#, r#""amount": "0.02""#);
+    let event: Event = serde_json::from_str(&quoted).unwrap();
+    assert_eq!(event.data.amount_f64_lossy(), Some(0.02));
+
+    let data: serde_json::Value = serde_json::from_str(&wire).unwrap();
+    let data: EventData = serde_json::from_value(data["data"].clone()).unwrap();
+    assert_eq!(data.price_f64_lossy(), Some(43210.0));
+}
+
+#[test]
+fn book_snapshots_arrive_as_data_events() {
+    let event: Event = serde_json::from_str(&common::fixture("ws_order_book.json")).unwrap();
+    assert_eq!(event.event, EventEvent::Data);
+    assert_eq!(event.channel, EventChannel::OrderBook(CurrencyPairs::Btcusd));
+    assert_eq!(event.check_consistency(), Ok(()));
+    let book = event.data.to_order_book().unwrap();
+    assert_eq!(book.bids.len(), 3);
+    assert_eq!(book.asks[0], ["43210.55", "0.02000000"]);
+
+    let event: Event = serde_json::from_str(&common::fixture("ws_detail_order_book.json")).unwrap();
+    assert_eq!(event.channel, EventChannel::DetailOrderBook(CurrencyPairs::Btcusd));
+    assert_eq!(event.data.to_order_book().unwrap().bids[0][2], "1405925551976448");
+}
+
+#[test]
+fn unknown_control_payload_is_kept_raw() {
+    let json = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {"server": "ws-3"}}"#;
+    let event: Event = serde_json::from_str(json).unwrap();
+    match event.data {
+        EventData::Raw(value) => assert_eq!(value["server"], "ws-3"),
+        other => panic!("expected raw data, got {:?}", other),
+    }
+    assert!(serde_json::from_str::<Event>(json).unwrap().check_consistency().is_ok());
+}
+
+#[test]
+fn events_compare_by_exact_fields() {
+    let trade = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+        "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+        "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+    let first: Event = serde_json::from_str(trade).unwrap();
+    let same_with_float_noise: Event = serde_json::from_str(&trade.replace(r#""amount": 0.1"#, r#""amount": 0.10000000000000002"#)).unwrap();
+    let next: Event = serde_json::from_str(&trade.replace(r#""id": 2"#, r#""id": 4"#)).unwrap();
+    assert_eq!(first, same_with_float_noise);
+    assert_ne!(first, next);
+
+    let unique: std::collections::HashSet<Event> = vec![first, same_with_float_noise, next].into_iter().collect();
+    assert_eq!(unique.len(), 2);
+}
+
+#[test]
+fn order_events_carry_their_action() {
+    let order = r#"{"event": "order_deleted", "channel": "live_orders_btcusd", "data": {"id": 1407531124125696, "id_str": "1407531124125696",
+        "order_type": 1, "datetime": "1633024800", "microtimestamp": "1633024800123456", "amount": 0.1, "amount_str": "0.10000000",
+        "price": 43210.55, "price_str": "43210.55"}}"#;
+    let event: Event = serde_json::from_str(order).unwrap();
+    assert_eq!(event.order_action(), Some(OrderAction::Deleted));
+    let event: Event = serde_json::from_str(&order.replace("order_deleted", "order_created")).unwrap();
+    assert_eq!(event.order_action(), Some(OrderAction::Created));
+
+    let subscribed = r#"{"event": "bts:subscription_succeeded", "channel": "live_orders_btcusd", "data": {}}"#;
+    assert_eq!(serde_json::from_str::<Event>(subscribed).unwrap().order_action(), None);
+}
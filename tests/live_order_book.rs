@@ -0,0 +1,130 @@
+use bitstamp::book::{DiffDecoder, LiveOrderBook, SequenceCheck};
+use bitstamp::types::{CurrencyPairs, Decimal, Event, EventChannel, EventData, OrderBook};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::time::Duration;
+
+fn levels(levels: &[(&str, &str)]) -> Vec<Vec<String>> {
+    levels.iter().map(|(p, a)| vec![p.to_string(), a.to_string()]).collect()
+}
+
+fn diff(microtimestamp: &str, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> EventData {
+    EventData::OrderBook {
+        timestamp: microtimestamp[..microtimestamp.len().min(10)].to_string(),
+        microtimestamp: microtimestamp.to_string(),
+        bids: levels(bids),
+        asks: levels(asks),
+    }
+}
+
+fn snapshot() -> OrderBook {
+    OrderBook {
+        timestamp: "1633024800".to_string(),
+        microtimestamp: "1633024800123456".to_string(),
+        bids: levels(&[("43205.12", "0.5"), ("43200.00", "1.25")]),
+        asks: levels(&[("43212.40", "0.1"), ("43220.00", "2")]),
+    }
+}
+
+#[test]
+fn applies_newer_diffs() {
+    let mut book = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    let result = book.apply(&diff("1633024800223456", &[("43205.12", "0"), ("43210.00", "0.3")], &[])).unwrap();
+    assert!(result.applied);
+    assert_eq!(book.best_bid().unwrap().price, Decimal::from_str("43210.00").unwrap());
+    assert_eq!(book.snapshot().bids.len(), 2);
+    assert_eq!(book.microtimestamp(), 1633024800223456);
+}
+
+#[test]
+fn microtimestamps_compare_numerically() {
+    let mut book = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    // Lexically "999999999999999" > "1633024800123456", numerically it's older
+    let stale = "999999999999999";
+    assert!(stale > "1633024800123456");
+    let result = book.apply(&diff(stale, &[("43205.12", "0")], &[])).unwrap();
+    assert!(!result.applied);
+    assert_eq!(book.best_bid().unwrap().price, Decimal::from_str("43205.12").unwrap());
+}
+
+#[test]
+fn flags_gaps_between_diffs() {
+    let mut book = LiveOrderBook::from_snapshot(&snapshot()).unwrap().with_max_gap(Duration::from_secs(2));
+    let result = book.apply(&diff("1633024801123456", &[("43210.00", "0.3")], &[])).unwrap();
+    assert!(result.applied && !result.sequence_gap_detected);
+    let result = book.apply(&diff("1633024805123456", &[("43211.00", "0.3")], &[])).unwrap();
+    assert!(result.applied && result.sequence_gap_detected);
+
+    let mut unchecked = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    assert!(!unchecked.apply(&diff("1633025800123456", &[], &[])).unwrap().sequence_gap_detected);
+}
+
+#[test]
+fn flags_out_of_order_diffs() {
+    let mut book = LiveOrderBook::from_snapshot(&snapshot()).unwrap().with_sequence_check(SequenceCheck::Warn);
+    // Older than the snapshot but in order, as diffs buffered during a resync are
+    let result = book.apply(&diff("1633024800023456", &[("43210.00", "0.3")], &[])).unwrap();
+    assert!(!result.applied && !result.out_of_order);
+    assert!(book.apply(&diff("1633024800223456", &[("43210.00", "0.3")], &[])).unwrap().applied);
+    let result = book.apply(&diff("1633024800200000", &[("43211.00", "0.3")], &[])).unwrap();
+    assert!(!result.applied && result.out_of_order);
+    let result = book.apply(&diff("1633024800223456", &[("43211.00", "0.3")], &[])).unwrap();
+    assert!(!result.applied && result.out_of_order);
+    assert_eq!(book.best_bid().unwrap().price, Decimal::from_str("43210.00").unwrap());
+
+    let mut strict = LiveOrderBook::from_snapshot(&snapshot()).unwrap().with_sequence_check(SequenceCheck::Strict);
+    strict.apply(&diff("1633024800223456", &[], &[])).unwrap();
+    let err = strict.apply(&diff("1633024800200000", &[("43211.00", "0.3")], &[])).unwrap_err();
+    assert!(err.to_string().contains("out of order"), "{}", err);
+    assert!(strict.apply(&diff("1633024800323456", &[], &[])).unwrap().applied);
+}
+
+#[test]
+fn streamed_book_converts_to_rest_book() {
+    let data = diff("1633024800223456", &[("43210.00", "0.3")], &[("43212.40", "0.1")]);
+    let book = data.to_order_book().unwrap();
+    assert_eq!(book.microtimestamp, "1633024800223456");
+    assert_eq!(OrderBook::try_from(data).unwrap(), book);
+    assert!(LiveOrderBook::from_snapshot(&book).is_ok());
+    assert!(EventData::Empty {}.to_order_book().is_none());
+}
+
+#[test]
+fn detects_crossed_book() {
+    let mut book = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    assert_eq!(book.check_consistency(), Ok(()));
+
+    book.apply(&diff("1633024800200000", &[("43212.40", "0.3")], &[])).unwrap();
+    assert!(book.check_consistency().unwrap_err().contains("crossed"));
+
+    book.apply(&diff("1633024800300000", &[("43212.40", "0")], &[("43230.00", "-1")])).unwrap();
+    assert!(book.check_consistency().unwrap_err().contains("43230.00"));
+}
+
+#[test]
+fn decoded_diffs_apply_like_events() {
+    let message = r#"{"data": {"timestamp": "1633024800", "microtimestamp": "1633024800223456",
+        "bids": [["43205.12", "0.00000000"], ["43210.00", "0.30000000"]], "asks": [["43212.40", "0.25000000"]]},
+        "channel": "diff_order_book_btcusd", "event": "data"}"#;
+    let channel = EventChannel::DiffOrderBook(CurrencyPairs::Btcusd);
+    let mut decoder = DiffDecoder::new();
+
+    let mut decoded = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    assert!(decoded.apply_diff(decoder.decode(message, &channel).unwrap()).unwrap().applied);
+    let mut parsed = LiveOrderBook::from_snapshot(&snapshot()).unwrap();
+    parsed.apply(&serde_json::from_str::<Event>(message).unwrap().data).unwrap();
+    assert_eq!(decoded, parsed);
+
+    // Buffers are reused, nothing of the previous diff is left over
+    let next = r#"{"data": {"timestamp": "1633024800", "microtimestamp": "1633024800323456", "bids": [], "asks": []},
+        "channel": "diff_order_book_btcusd", "event": "data"}"#;
+    let diff = decoder.decode(next, &channel).unwrap();
+    assert!(diff.bids.is_empty() && diff.asks.is_empty());
+    assert_eq!(diff.microtimestamp, 1633024800323456);
+
+    // Anything else is left for `Event` parsing
+    assert!(decoder.decode(message, &EventChannel::DiffOrderBook(CurrencyPairs::Etheur)).is_none());
+    assert!(decoder.decode(&message.replace("43210.00", "not a price"), &channel).is_none());
+    let subscribed = r#"{"event": "bts:subscription_succeeded", "channel": "diff_order_book_btcusd", "data": {}}"#;
+    assert!(decoder.decode(subscribed, &channel).is_none());
+}
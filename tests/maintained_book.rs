@@ -0,0 +1,136 @@
+mod common;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use bitstamp::book::SequenceCheck;
+use bitstamp::types::{CurrencyPairs, Decimal};
+use common::FixtureTransport;
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+fn diff(microtimestamp: &str, bid: &str) -> Message {
+    Message::Text(format!(
+        r#"{{"event": "data", "channel": "diff_order_book_btcusd", "data": {{"timestamp": "1633024800",
+        "microtimestamp": "{}", "bids": [["{}", "0.25"]], "asks": []}}}}"#,
+        microtimestamp, bid
+    ))
+}
+
+#[tokio::test]
+async fn applies_diffs_newer_than_snapshot() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        // Wait for the subscription
+        ws.next().await.unwrap().unwrap();
+        ws.send(Message::Text(r#"{"event": "bts:subscription_succeeded", "channel": "diff_order_book_btcusd", "data": {}}"#.to_string())).await.unwrap();
+        // Older than the snapshot, must be skipped
+        ws.send(diff("1633024800000000", "50000.00")).await.unwrap();
+        ws.send(diff("1633024800200000", "43208.00")).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let transport = FixtureTransport::new().route("order_book/btcusd/", StatusCode::OK, "order_book.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .ws_url(&url)
+        .build();
+
+    let mut book = bts.maintained_order_book(CurrencyPairs::Btcusd).await.unwrap();
+    let live = book.next().await.unwrap();
+    assert_eq!(live.microtimestamp(), 1633024800200000);
+    assert_eq!(live.best_bid().unwrap().price, Decimal::from_str("43208.00").unwrap());
+    assert_eq!(book.resyncs(), 0);
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn resyncs_crossed_book() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        // Bid above the snapshot's best ask
+        ws.send(diff("1633024800200000", "43215.00")).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let transport = FixtureTransport::new().route("order_book/btcusd/", StatusCode::OK, "order_book.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .ws_url(&url)
+        .build();
+
+    let mut book = bts.maintained_order_book(CurrencyPairs::Btcusd).await.unwrap();
+    let live = book.next().await.unwrap();
+    assert_eq!(live.best_bid().unwrap().price, Decimal::from_str("43205.12").unwrap());
+    assert_eq!(book.resyncs(), 1);
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn dedup_skips_repeated_diffs() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        // Resent as after a reconnect, out of order for the strict sequence check
+        ws.send(diff("1633024800200000", "43208.00")).await.unwrap();
+        ws.send(diff("1633024800200000", "43208.00")).await.unwrap();
+        ws.send(diff("1633024800300000", "43209.00")).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let transport = FixtureTransport::new().route("order_book/btcusd/", StatusCode::OK, "order_book.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .ws_url(&url)
+        .build();
+
+    let mut book = bts.maintained_order_book(CurrencyPairs::Btcusd).await.unwrap();
+    book.set_sequence_check(SequenceCheck::Strict);
+    book.set_dedup(Duration::from_secs(60));
+    assert_eq!(book.next().await.unwrap().microtimestamp(), 1633024800200000);
+    let live = book.next().await.unwrap();
+    assert_eq!(live.microtimestamp(), 1633024800300000);
+    assert_eq!(live.best_bid().unwrap().price, Decimal::from_str("43209.00").unwrap());
+    assert_eq!(book.resyncs(), 0);
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn full_consistency_check_is_opt_in() {
+    for full_check in [false, true] {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            ws.next().await.unwrap().unwrap();
+            // A negative amount deep in the book, which only the full check looks at
+            ws.send(diff("1633024800200000", "43100.00").into_text().unwrap().replace("0.25", "-0.25").into()).await.unwrap();
+            while ws.next().await.is_some() {}
+        });
+
+        let transport = FixtureTransport::new().route("order_book/btcusd/", StatusCode::OK, "order_book.json");
+        let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+            .transport(transport.clone())
+            .ws_url(&url)
+            .build();
+
+        let mut book = bts.maintained_order_book(CurrencyPairs::Btcusd).await.unwrap();
+        book.set_full_consistency_check(full_check);
+        book.next().await.unwrap();
+        assert_eq!(book.resyncs(), full_check as u64);
+        assert_eq!(transport.requests().len(), 1 + full_check as usize);
+    }
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use bitstamp::nonce::{FileNonceStore, MonotonicNonceGenerator, NonceGenerator, NonceStore, UuidNonceGenerator};
+use common::FixtureTransport;
+use hyper::StatusCode;
+
+async fn concurrent_nonces<N: NonceGenerator + 'static>(nonce_generator: N) -> Vec<String> {
+    let transport = FixtureTransport::new().route("balance/", StatusCode::OK, "balance.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .nonce_generator(nonce_generator)
+        .build();
+    let tasks: Vec<_> = (0..100)
+        .map(|_| {
+            let bts = bts.clone();
+            tokio::spawn(async move { bts.get_balance().await.unwrap() })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    transport
+        .request_headers()
+        .iter()
+        .map(|h| h["x-auth-nonce"].to_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn concurrent_requests_get_unique_nonces() {
+    let nonces = concurrent_nonces(UuidNonceGenerator).await;
+    assert_eq!(nonces.iter().collect::<HashSet<_>>().len(), 100);
+
+    // Handed out without gaps or repeats, so strictly increasing in the order generated
+    let mut nonces = concurrent_nonces(MonotonicNonceGenerator::starting_at(1000)).await;
+    nonces.sort();
+    let expected: Vec<String> = (1001..=1100).map(|n| format!("{:036}", n)).collect();
+    assert_eq!(nonces, expected);
+}
+
+#[test]
+fn monotonic_generator_increases_per_thread() {
+    let generator = Arc::new(MonotonicNonceGenerator::starting_at(0));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let generator = generator.clone();
+            std::thread::spawn(move || (0..1000).map(|_| generator.next()).collect::<Vec<_>>())
+        })
+        .collect();
+    let mut all = HashSet::new();
+    for thread in threads {
+        let nonces = thread.join().unwrap();
+        assert!(nonces.windows(2).all(|w| w[0] < w[1]));
+        all.extend(nonces);
+    }
+    assert_eq!(all.len(), 8000);
+}
+
+#[derive(Clone, Default)]
+struct MemoryStore(Arc<Mutex<Option<u64>>>);
+
+impl NonceStore for MemoryStore {
+    fn load(&self) -> std::io::Result<Option<u64>> {
+        Ok(*self.0.lock().unwrap())
+    }
+
+    fn save(&self, mark: u64) -> std::io::Result<()> {
+        *self.0.lock().unwrap() = Some(mark);
+        Ok(())
+    }
+}
+
+#[test]
+fn restart_resumes_above_previous_nonces() {
+    // A mark far ahead of the clock, so only the store decides where nonces resume
+    let start = chrono::Utc::now().timestamp_micros() as u64 * 2;
+    let store = MemoryStore::default();
+    store.save(start).unwrap();
+
+    let before = MonotonicNonceGenerator::with_store(store.clone()).unwrap();
+    let last = (0..25_000).map(|_| before.next()).last().unwrap();
+    assert_eq!(last, format!("{:036}", start + 25_000));
+    assert!(store.load().unwrap().unwrap() >= start + 25_000);
+    drop(before);
+
+    let after = MonotonicNonceGenerator::with_store(store).unwrap();
+    assert!(after.next() > last);
+}
+
+#[test]
+fn file_store_round_trips() {
+    let path = std::env::temp_dir().join(format!("bitstamp-nonce-{}", uuid::Uuid::new_v4()));
+    let store = FileNonceStore::new(&path);
+    assert_eq!(store.load().unwrap(), None);
+    store.save(1234).unwrap();
+    assert_eq!(store.load().unwrap(), Some(1234));
+
+    let nonce = MonotonicNonceGenerator::with_store(store.clone()).unwrap().next();
+    assert!(nonce > format!("{:036}", 1234));
+    assert!(store.load().unwrap().unwrap() > chrono::Utc::now().timestamp_micros() as u64);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Stores like `MemoryStore` but fails every save while `failing` is set
+#[derive(Clone, Default)]
+struct FlakyStore {
+    mark: MemoryStore,
+    failing: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl NonceStore for FlakyStore {
+    fn load(&self) -> std::io::Result<Option<u64>> {
+        self.mark.load()
+    }
+
+    fn save(&self, mark: u64) -> std::io::Result<()> {
+        if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+            return Err(std::io::Error::other("disk full"));
+        }
+        self.mark.save(mark)
+    }
+}
+
+#[tokio::test]
+async fn failed_save_fails_the_request() {
+    let store = FlakyStore::default();
+    let nonce_generator = MonotonicNonceGenerator::with_store(store.clone()).unwrap();
+    let reserved = store.load().unwrap().unwrap();
+    // Use up the saved reservation
+    let last = (0..10_000).map(|_| nonce_generator.try_next().unwrap()).last().unwrap();
+    assert_eq!(last, format!("{:036}", reserved));
+
+    let transport = FixtureTransport::new().route("balance/", StatusCode::OK, "balance.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .nonce_generator(nonce_generator)
+        .build();
+    store.failing.store(true, std::sync::atomic::Ordering::SeqCst);
+    let err = bts.get_balance().await.unwrap_err();
+    assert!(err.to_string().contains("disk full"), "{}", err);
+    assert!(transport.request_headers().is_empty());
+    assert_eq!(store.load().unwrap(), Some(reserved));
+
+    store.failing.store(false, std::sync::atomic::Ordering::SeqCst);
+    bts.get_balance().await.unwrap();
+    let sent = transport.request_headers()[0]["x-auth-nonce"].to_str().unwrap().to_string();
+    assert!(sent > last);
+    assert!(store.load().unwrap().unwrap() > reserved);
+}
+
+#[test]
+fn next_does_not_panic_when_the_save_fails() {
+    let store = FlakyStore::default();
+    let nonce_generator = MonotonicNonceGenerator::with_store(store.clone()).unwrap();
+    let reserved = store.load().unwrap().unwrap();
+    let last = (0..10_000).map(|_| nonce_generator.next()).last().unwrap();
+
+    store.failing.store(true, std::sync::atomic::Ordering::SeqCst);
+    assert!(nonce_generator.try_next().is_err());
+    let next = nonce_generator.next();
+    assert!(next > last);
+    assert_eq!(store.load().unwrap(), Some(reserved));
+}
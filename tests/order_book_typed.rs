@@ -0,0 +1,68 @@
+mod common;
+
+use std::str::FromStr;
+
+use bitstamp::types::{Decimal, OrderBook, OrderBookTyped, PriceLevel, TradeSide};
+
+fn book() -> OrderBookTyped {
+    let book: OrderBook = serde_json::from_str(&common::fixture("order_book.json")).unwrap();
+    book.typed().unwrap()
+}
+
+#[test]
+fn mid_price_and_spread() {
+    let book = book();
+    assert_eq!(book.spread(), Some(Decimal::from_str("7.28").unwrap()));
+    assert_eq!(book.mid_price(), Some(Decimal::from_str("43208.76").unwrap()));
+    assert_eq!(book.spread_bps().unwrap().round_dp(6), Decimal::from_str("1.684844").unwrap());
+
+    let one_sided = OrderBookTyped { asks: Vec::new(), ..book };
+    assert_eq!(one_sided.mid_price(), None);
+    assert_eq!(one_sided.spread_bps(), None);
+    assert_eq!(OrderBookTyped::default().spread(), None);
+}
+
+#[test]
+fn estimate_fill_walks_the_book() {
+    let book = book();
+    let d = |s: &str| Decimal::from_str(s).unwrap();
+
+    let buy = book.estimate_fill(TradeSide::Buy, d("1"));
+    assert_eq!(buy.filled, d("1"));
+    assert!(buy.unfilled.is_zero());
+    assert_eq!(buy.cost, d("43219.24"));
+    assert_eq!(buy.average_price, Some(d("43219.24")));
+    assert_eq!(buy.worst_price, Some(d("43220.00")));
+
+    // More than the bids hold
+    let sell = book.estimate_fill(TradeSide::Sell, d("2"));
+    assert_eq!(sell.filled, d("1.75"));
+    assert_eq!(sell.unfilled, d("0.25"));
+    assert_eq!(sell.average_price.unwrap().round_dp(6), d("43201.462857"));
+    assert_eq!(sell.worst_price, Some(d("43200.00")));
+
+    let empty = OrderBookTyped::default().estimate_fill(TradeSide::Buy, d("1"));
+    assert_eq!(empty.unfilled, d("1"));
+    assert_eq!(empty.average_price, None);
+    assert_eq!(empty.worst_price, None);
+}
+
+#[test]
+fn overflowing_levels_leave_the_rest_unfilled() {
+    let d = |s: &str| Decimal::from_str(s).unwrap();
+    let huge = Decimal::new(i128::MAX / 2 + 1, 0);
+    let book = OrderBookTyped {
+        asks: vec![PriceLevel { price: d("100"), amount: d("1") }, PriceLevel { price: huge, amount: d("20") }],
+        bids: vec![PriceLevel { price: Decimal::new(i128::MAX, 0), amount: d("1") }],
+        ..Default::default()
+    };
+
+    let buy = book.estimate_fill(TradeSide::Buy, d("3"));
+    assert_eq!(buy.filled, d("1"));
+    assert_eq!(buy.unfilled, d("2"));
+    assert_eq!(buy.cost, d("100"));
+    assert_eq!(buy.worst_price, Some(d("100")));
+
+    // Adding the two prices overflows
+    assert_eq!(book.mid_price(), None);
+}
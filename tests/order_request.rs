@@ -0,0 +1,25 @@
+use bitstamp::types::{LimitOrderRequest, TimeInForce};
+use serde_json::json;
+
+fn request(time_in_force: TimeInForce) -> serde_json::Value {
+    let request = LimitOrderRequest { amount: "0.01".to_string(), price: "43000".to_string(), time_in_force, ..Default::default() };
+    serde_json::to_value(request).unwrap()
+}
+
+#[test]
+fn time_in_force_maps_to_order_flags() {
+    assert_eq!(request(TimeInForce::GoodTillCancelled), json!({"amount": "0.01", "price": "43000"}));
+    assert_eq!(request(TimeInForce::Day), json!({"amount": "0.01", "price": "43000", "daily_order": true}));
+    assert_eq!(request(TimeInForce::ImmediateOrCancel), json!({"amount": "0.01", "price": "43000", "ioc_order": true}));
+    assert_eq!(request(TimeInForce::FillOrKill), json!({"amount": "0.01", "price": "43000", "fok_order": true}));
+    assert_eq!(
+        request(TimeInForce::GoodTillDate(1700000000)),
+        json!({"amount": "0.01", "price": "43000", "gtd_order": true, "expire_time": 1700000000})
+    );
+}
+
+#[test]
+fn post_only_maps_to_moc_order() {
+    let request = LimitOrderRequest { amount: "0.01".to_string(), price: "43000".to_string(), post_only: true, ..Default::default() };
+    assert_eq!(serde_json::to_value(request).unwrap(), json!({"amount": "0.01", "price": "43000", "moc_order": true}));
+}
@@ -0,0 +1,42 @@
+use std::str::FromStr;
+
+use bitstamp::types::{Decimal, PairInfo};
+
+fn d(s: &str) -> Decimal {
+    Decimal::from_str(s).unwrap()
+}
+
+fn btcusd() -> PairInfo {
+    PairInfo {
+        base_decimals: 8,
+        counter_decimals: 2,
+        url_symbol: "btcusd".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn amounts_truncate_to_base_decimals() {
+    let info = btcusd();
+    assert_eq!(info.round_amount(d("0.123456789")).to_string(), "0.12345678");
+    assert_eq!(info.round_amount(d("0.999999999")).to_string(), "0.99999999");
+    assert_eq!(info.round_amount(d("1.5")), d("1.5"));
+}
+
+#[test]
+fn prices_round_to_counter_decimals() {
+    let info = btcusd();
+    assert_eq!(info.round_price(d("43210.554")).to_string(), "43210.55");
+    assert_eq!(info.round_price(d("43210.555")).to_string(), "43210.56");
+    assert_eq!(info.round_price(d("43210.1")), d("43210.1"));
+}
+
+#[test]
+fn high_scale_prices_round_without_overflow() {
+    let info = btcusd();
+    // Rounding 66 decimal places to 28 compares the remainder with 10^38
+    let price = Decimal::new(9 * 10i128.pow(37), 66);
+    assert_eq!(price, Decimal::new(1, 28));
+    assert_eq!(info.round_price(price), Decimal::ZERO);
+    assert_eq!(info.round_price(d("43210.5549999999999999999999")).to_string(), "43210.55");
+}
@@ -0,0 +1,129 @@
+use bitstamp::types::{CurrencyPairs, EventChannel};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Answer the first subscribe request with `reply`, returns the server url
+async fn serve_reply(reply: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let subscribe = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains("bts:subscribe"));
+        ws.send(Message::Text(reply.to_string())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+    url
+}
+
+#[tokio::test]
+async fn rejected_token_is_unauthorized() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let subscribe = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains(r#""auth":"expired-token""#));
+        ws.send(Message::Text(
+            r#"{"event": "bts:subscription_failed", "channel": "private-my_orders_btcusd-123", "data": {"message": "Invalid token"}}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream
+        .subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "expired-token")
+        .await
+        .unwrap_err();
+    assert!(err.is_unauthorized());
+    assert!(stream.subscriptions().is_empty());
+    assert_eq!(stream.stats().parse_failures, 0);
+}
+
+#[tokio::test]
+async fn bts_error_fails_subscription() {
+    let url = serve_reply(r#"{"event": "bts:error", "channel": "", "data": {"code": null, "message": "Bad subscription string."}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let started = std::time::Instant::now();
+    let err = stream
+        .subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "token")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Bad subscription string."), "{}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    assert!(stream.subscriptions().is_empty());
+}
+
+#[tokio::test]
+async fn error_status_data_fails_subscription() {
+    let url = serve_reply(
+        r#"{"event": "bts:subscription_succeeded", "channel": "private-my_orders_btcusd-123", "data": {"status": "error", "reason": "Subscription rejected"}}"#,
+    )
+    .await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let started = std::time::Instant::now();
+    let err = stream
+        .subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "token")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Subscription rejected"), "{}", err);
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    assert!(stream.subscriptions().is_empty());
+    assert_eq!(stream.stats().parse_failures, 0);
+}
+
+#[tokio::test]
+async fn error_status_data_on_public_channel() {
+    let url = serve_reply(r#"{"event": "data", "channel": "order_book_btcusd", "data": {"status": "error", "message": "Unknown channel"}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    stream.subscribe(EventChannel::OrderBook(CurrencyPairs::Btcusd)).await.unwrap();
+    let err = stream.next().await.unwrap_err();
+    assert!(err.to_string().contains("Unknown channel"), "{}", err);
+    assert!(!err.is_unauthorized());
+    assert_eq!(stream.stats().parse_failures, 0);
+}
+
+#[tokio::test]
+async fn private_channel_error_is_classified_by_code() {
+    // Not a subscription rejection, so the private channel alone doesn't make it unauthorized
+    let url = serve_reply(r#"{"event": "bts:error", "channel": "private-my_orders_btcusd-123", "data": {"code": null, "message": "Rate limit exceeded"}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream.subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "token").await.unwrap_err();
+    assert!(err.to_string().contains("Rate limit exceeded"), "{}", err);
+    assert!(!err.is_unauthorized());
+
+    let url = serve_reply(r#"{"event": "bts:error", "channel": "", "data": {"code": "API0002", "message": "Missing permission"}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream.subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "token").await.unwrap_err();
+    assert!(err.is_unauthorized());
+
+    // Only the code counts, not a message mentioning a token
+    let url = serve_reply(r#"{"event": "bts:error", "channel": "", "data": {"code": "API0016", "message": "Token bucket exhausted"}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream.subscribe_private(EventChannel::MyOrders(CurrencyPairs::Btcusd, 123), "token").await.unwrap_err();
+    assert!(!err.is_unauthorized());
+}
+
+#[tokio::test]
+async fn public_error_mentioning_a_token_is_not_unauthorized() {
+    let url = serve_reply(r#"{"event": "bts:error", "channel": "", "data": {"code": null, "message": "Invalid token in request format"}}"#).await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    stream.subscribe(EventChannel::LiveTrades(CurrencyPairs::Btcusd)).await.unwrap();
+    let err = stream.next().await.unwrap_err();
+    assert!(err.to_string().contains("Invalid token in request format"), "{}", err);
+    assert!(!err.is_unauthorized());
+}
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use bitstamp::types::{ConnectionEventKind, CurrencyPairs, EventChannel, EventEvent};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+    "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+    "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+const CONFIRMED: &str = r#"{"event": "bts:subscription_succeeded", "channel": "private-my_orders_btcusd-123", "data": {}}"#;
+
+#[tokio::test]
+async fn resubscribes_in_order_before_new_events() {
+    const PAUSE: Duration = Duration::from_millis(200);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (resent, mut resent_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.send(Message::Text(CONFIRMED.to_string())).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.close(None).await.unwrap();
+
+        let (socket, _) = listener.accept().await.unwrap();
+        // Holding the handshake back keeps the connection down for at least this long
+        tokio::time::sleep(PAUSE).await;
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        for _ in 0..3 {
+            resent.send(ws.next().await.unwrap().unwrap().into_text().unwrap()).unwrap();
+        }
+        // Data ahead of the private confirmation has to wait behind the reconnect markers
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        ws.send(Message::Text(CONFIRMED.to_string())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_auto_reconnect(true)
+        .ws_reconnect_delay(Duration::from_millis(10))
+        .build();
+    let channels = [
+        EventChannel::LiveTrades(CurrencyPairs::Btcusd),
+        EventChannel::MyOrders(CurrencyPairs::Btcusd, 123),
+        EventChannel::DiffOrderBook(CurrencyPairs::Etheur),
+    ];
+    let mut stream = bts.event_stream().await.unwrap();
+    stream.subscribe(channels[0].clone()).await.unwrap();
+    stream.subscribe_private(channels[1].clone(), "token").await.unwrap();
+    stream.subscribe(channels[2].clone()).await.unwrap();
+
+    for channel in channels.iter() {
+        let event = stream.next().await.unwrap();
+        assert_eq!(event.event, EventEvent::Reconnected);
+        assert_eq!(event.channel, *channel);
+    }
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+    assert_eq!(stream.stats().reconnects, 1);
+    let history: Vec<ConnectionEventKind> = stream.connection_history().iter().map(|e| e.kind).collect();
+    assert_eq!(history, [ConnectionEventKind::Connected, ConnectionEventKind::Disconnected, ConnectionEventKind::Reconnected]);
+    assert!(stream.connection_history()[1].reason.is_some());
+    assert!(stream.last_gap().unwrap() >= PAUSE);
+
+    let resent: Vec<String> = (0..3).map(|_| resent_rx.try_recv().unwrap()).collect();
+    assert!(resent[0].contains("live_trades_btcusd"));
+    assert!(resent[1].contains("private-my_orders_btcusd-123") && resent[1].contains(r#""auth":"token""#));
+    assert!(resent[2].contains("diff_order_book_etheur"));
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        // Close and stop listening, so every reconnect is refused
+        drop(listener);
+        ws.close(None).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_auto_reconnect(true)
+        .ws_reconnect_delay(Duration::from_millis(1))
+        .ws_max_reconnect_attempts(3)
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream.next().await.unwrap_err();
+    assert!(err.is_websocket(), "{}", err);
+    assert!(err.to_string().contains("after 3 failed reconnect attempts"));
+    assert!(std::error::Error::source(&err).is_some());
+    assert_eq!(stream.stats().reconnects, 0);
+    assert_eq!(stream.connection_history().len(), 2);
+    assert_eq!(stream.last_gap(), None);
+}
+
+#[tokio::test]
+async fn first_retry_is_immediate() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.close(None).await.unwrap();
+        let closed = std::time::Instant::now();
+        drop(ws);
+
+        // Fail the first attempt's handshake
+        let (socket, _) = listener.accept().await.unwrap();
+        let first_retry = std::time::Instant::now();
+        drop(socket);
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let second_retry = std::time::Instant::now();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        tokio::spawn(async move { while ws.next().await.is_some() {} });
+        (first_retry - closed, second_retry - first_retry)
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_auto_reconnect(true)
+        .ws_reconnect_delay(Duration::from_millis(300))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+    assert_eq!(stream.stats().reconnects, 1);
+
+    let (first, second) = server.await.unwrap();
+    assert!(first < Duration::from_millis(200), "first retry after {:?}", first);
+    assert!(second >= Duration::from_millis(300), "second retry after {:?}", second);
+}
+
+#[tokio::test]
+async fn reconnect_without_subscriptions_only_shows_in_history() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.close(None).await.unwrap();
+        drop(ws);
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_auto_reconnect(true)
+        .ws_reconnect_delay(Duration::from_millis(10))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    // No channel to mark, so the first event is the one from the new connection
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+    assert_eq!(stream.stats().reconnects, 1);
+    let history: Vec<ConnectionEventKind> = stream.connection_history().iter().map(|e| e.kind).collect();
+    assert_eq!(history, [ConnectionEventKind::Connected, ConnectionEventKind::Disconnected, ConnectionEventKind::Reconnected]);
+}
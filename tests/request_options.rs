@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitstamp::transport::{Transport, TransportFuture};
+use bitstamp::{Bitstamp, RequestOptions};
+use hyper::{Body, Request, Response};
+
+/// Answers every request with an empty order book after `delay`
+#[derive(Clone)]
+struct SlowTransport {
+    delay: Duration,
+    calls: Arc<AtomicU32>,
+}
+
+impl Transport for SlowTransport {
+    fn request(&self, _request: Request<Body>) -> TransportFuture {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let delay = self.delay;
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            let body = r#"{"timestamp": "1633024800", "microtimestamp": "1633024800123456", "bids": [], "asks": []}"#;
+            Ok(Response::new(Body::from(body)))
+        })
+    }
+}
+
+#[tokio::test]
+async fn per_call_timeout_and_retries_override_client() {
+    let transport = SlowTransport { delay: Duration::from_millis(200), calls: Arc::new(AtomicU32::new(0)) };
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .request_timeout(Duration::from_millis(20))
+        .retry_backoff(Duration::from_millis(1))
+        .build();
+
+    let err = bts.get_order_book("btcusd", None).await.unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(transport.calls.load(Ordering::SeqCst), 1);
+
+    let options = RequestOptions::default().timeout(Duration::from_millis(20)).max_retries(2);
+    let err = bts.get_order_book_with_options("btcusd", None, options).await.unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(transport.calls.load(Ordering::SeqCst), 4);
+
+    let options = RequestOptions::default().timeout(Duration::from_secs(5));
+    let book = bts.get_order_book_with_options("btcusd", None, options).await.unwrap();
+    assert_eq!(book.microtimestamp, "1633024800123456");
+}
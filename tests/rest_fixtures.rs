@@ -0,0 +1,605 @@
+mod common;
+
+use common::{number, FixtureTransport};
+use hyper::StatusCode;
+
+#[tokio::test]
+async fn public_endpoints_deserialize() {
+    let transport = FixtureTransport::new()
+        .route("ticker/btcusd/", StatusCode::OK, "ticker.json")
+        .route("ticker_hour/btcusd/", StatusCode::OK, "ticker.json")
+        .route("order_book/btcusd/", StatusCode::OK, "order_book.json")
+        .route("transactions/btcusd/?time=hour", StatusCode::OK, "transactions.json")
+        .route("trading-pairs-info/", StatusCode::OK, "trading_pairs_info.json")
+        .route("eur_usd/", StatusCode::OK, "eur_usd.json");
+    let bts = transport.client();
+
+    let ticker = bts.get_ticker("btcusd").await.unwrap();
+    assert_eq!(ticker.last, "43210.55");
+    assert_eq!(bts.get_hourly_ticker("btcusd").await.unwrap(), ticker);
+    assert_eq!(bts.get_ticker(bitstamp::types::CurrencyPairs::Btcusd).await.unwrap(), ticker);
+
+    let book = bts.get_order_book("btcusd", None).await.unwrap();
+    assert_eq!(book.microtimestamp, "1633024800123456");
+    assert_eq!(book.bids[0], vec![number("43205.12"), number("0.50000000")]);
+    assert_eq!(book.asks.len(), 2);
+
+    let transactions = bts.get_transactions("btcusd", Some(bitstamp::types::Time::Hour)).await.unwrap();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1].type_field, "1");
+
+    let pairs = bts.get_trading_pairs_info().await.unwrap();
+    assert_eq!(pairs.len(), 15);
+    assert_eq!(pairs[0].url_symbol, "btcusd");
+
+    let rate = bts.get_eur_usd().await.unwrap();
+    assert_eq!(rate.buy, "1.16010");
+    assert_eq!(rate.buy_decimal().unwrap(), "1.1601".parse().unwrap());
+    assert_eq!(rate.sell_decimal().unwrap(), "1.1552".parse().unwrap());
+    assert_eq!(rate.spread().unwrap(), "0.0049".parse().unwrap());
+}
+
+#[tokio::test]
+async fn private_endpoints_deserialize() {
+    let transport = FixtureTransport::new()
+        .route("balance/", StatusCode::OK, "balance.json")
+        .route("buy/btcusd/", StatusCode::OK, "buy_order.json");
+    let bts = transport.client();
+
+    let balance = bts.get_balance().await.unwrap();
+    assert_eq!(balance.btc_available, number("0.52000000"));
+    assert_eq!(balance.btcusd_fee, number("0.500"));
+
+    let order = bts.buy_limit_order("btcusd", "0.01", "43000.00").await.unwrap();
+    assert_eq!(order.id, "1407531124125696");
+    assert_eq!(order.type_field, "0");
+}
+
+#[tokio::test]
+async fn v1_error() {
+    let bts = FixtureTransport::new()
+        .route("ticker/dogeusd/", StatusCode::NOT_FOUND, "error_v1.json")
+        .client();
+
+    let err = bts.get_ticker("dogeusd").await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+    assert!(err.to_string().contains("Invalid currency pair"));
+}
+
+#[tokio::test]
+async fn v2_error_with_string_reason() {
+    let bts = FixtureTransport::new()
+        .route("balance/", StatusCode::FORBIDDEN, "error_v2_reason_string.json")
+        .client();
+
+    let err = bts.get_balance().await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::FORBIDDEN));
+    assert!(err.to_string().contains("Invalid signature (API0005)"));
+}
+
+#[tokio::test]
+async fn v2_error_with_object_reason() {
+    let bts = FixtureTransport::new()
+        .route("buy/btcusd/", StatusCode::OK, "error_v2_reason_object.json")
+        .client();
+
+    let err = bts.buy_limit_order("btcusd", "1", "43210.55").await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::OK));
+    assert!(err.to_string().contains("You need 43210.55 USD to open that order"));
+    assert!(err.to_string().contains("API0016"));
+}
+
+#[tokio::test]
+async fn maintenance_page() {
+    let bts = FixtureTransport::new()
+        .route("ticker/btcusd/", StatusCode::SERVICE_UNAVAILABLE, "maintenance.html")
+        .route("eur_usd/", StatusCode::OK, "maintenance.html")
+        .client();
+
+    let err = bts.get_ticker("btcusd").await.unwrap_err();
+    assert_eq!(err.status(), Some(StatusCode::SERVICE_UNAVAILABLE));
+
+    let err = bts.get_eur_usd().await.unwrap_err();
+    assert_eq!(err.status(), None);
+}
+
+#[tokio::test]
+async fn order_book_if_changed() {
+    let bts = FixtureTransport::new()
+        .route("order_book/btcusd/", StatusCode::OK, "order_book.json")
+        .client();
+
+    let book = bts.get_order_book_if_changed("btcusd", 0).await.unwrap().unwrap();
+    assert_eq!(book.microtimestamp, "1633024800123456");
+    assert!(bts.get_order_book_if_changed("btcusd", 1633024800123456).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn destination_tags() {
+    let transport = FixtureTransport::new()
+        .route("xrp_address/", StatusCode::OK, "xrp_address.json")
+        .route_body("xlm_address/", StatusCode::OK, r#"{"address": "GABFQIK63R2NETJM7T673EAMZN4RJLLGP3OFUEJU5SZVTGWUKULZJNL6"}"#.to_string())
+        .route("xrp_withdrawal/", StatusCode::OK, "withdrawal.json");
+    let bts = transport.client();
+
+    let address = bts.deposit_address("xrp").await.unwrap();
+    assert_eq!(address.destination_tag.as_deref(), Some("89123456"));
+    assert!(bts.deposit_address("xlm").await.is_err());
+
+    let mut request = bitstamp::types::WithdrawalRequest {
+        amount: "25".to_string(),
+        address: "rDsbeomae4FXwgQTJp9Rs64Qg9vDiTCdBv".to_string(),
+        destination_tag: None,
+    };
+    assert!(bts.withdrawal("XRP", request.clone()).await.is_err());
+    assert!(!transport.requests().iter().any(|r| r.contains("withdrawal")));
+
+    request.destination_tag = Some("89123456".to_string());
+    assert_eq!(bts.withdrawal("XRP", request).await.unwrap().id, "1234567");
+}
+
+#[tokio::test]
+async fn verify_credentials() {
+    let bts = FixtureTransport::new().route("balance/", StatusCode::OK, "balance.json").client();
+    bts.verify_credentials().await.unwrap();
+
+    let bts = FixtureTransport::new()
+        .route("balance/", StatusCode::FORBIDDEN, "error_v2_reason_string.json")
+        .client();
+    let err = bts.verify_credentials().await.unwrap_err();
+    assert!(err.is_unauthorized());
+    assert!(err.to_string().contains("check the secret"));
+}
+
+#[tokio::test]
+async fn public_cache_coalesces_requests() {
+    let transport = FixtureTransport::new()
+        .route("order_book/btcusd/", StatusCode::OK, "order_book.json")
+        .route("order_book/etheur/", StatusCode::OK, "order_book.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .public_cache_ttl(std::time::Duration::from_secs(60))
+        .build();
+
+    let books = futures::future::join_all((0..5).map(|_| bts.get_order_book("btcusd", None))).await;
+    assert!(books.iter().all(|book| book.as_ref().unwrap().microtimestamp == "1633024800123456"));
+    bts.get_order_book("etheur", None).await.unwrap();
+    bts.get_order_book("btcusd", None).await.unwrap();
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn pairs_info_map_is_cached() {
+    let transport = FixtureTransport::new().route("trading-pairs-info/", StatusCode::OK, "trading_pairs_info.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .public_cache_ttl(std::time::Duration::from_secs(60))
+        .build();
+
+    let pairs = bts.get_pairs_info_map().await.unwrap();
+    assert_eq!(pairs.len(), 15);
+    assert_eq!(pairs["btcusd"].url_symbol, "btcusd");
+    assert_eq!(pairs["btcusd"], bts.get_pair_info(bitstamp::types::CurrencyPairs::Btcusd).await.unwrap());
+    assert_eq!(bts.get_pairs_info_map().await.unwrap(), pairs);
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn requests_ask_for_json() {
+    let transport = FixtureTransport::new()
+        .route("ticker/btcusd/", StatusCode::OK, "ticker.json")
+        .route("balance/", StatusCode::OK, "balance.json");
+    let bts = transport.client();
+    bts.get_ticker("btcusd").await.unwrap();
+    bts.get_balance().await.unwrap();
+
+    let headers = transport.request_headers();
+    assert_eq!(headers.len(), 2);
+    assert!(headers.iter().all(|h| h["accept"] == "application/json"));
+}
+
+#[tokio::test]
+async fn post_parameters_are_form_encoded_and_signed() {
+    use hmac::{Hmac, Mac, NewMac};
+
+    let transport = FixtureTransport::new().route("buy/btcusd/", StatusCode::OK, "buy_order.json");
+    let bts = transport.client();
+    let request = bitstamp::types::LimitOrderRequest {
+        amount: "0.01".to_string(),
+        price: "43000.00".to_string(),
+        client_order_id: Some("my id&1".to_string()),
+        time_in_force: bitstamp::types::TimeInForce::ImmediateOrCancel,
+        ..Default::default()
+    };
+    bts.limit_order(bitstamp::types::TradeSide::Buy, bitstamp::types::CurrencyPairs::Btcusd, request).await.unwrap();
+
+    let body = &transport.request_bodies()[0];
+    assert_eq!(body, "amount=0.01&client_order_id=my+id%261&ioc_order=True&price=43000.00");
+    let headers = &transport.request_headers()[0];
+    assert_eq!(headers["content-type"], "application/x-www-form-urlencoded");
+    let message = format!(
+        "BITSTAMP keyPOSTwww.bitstamp.net/api/v2/buy/btcusd/application/x-www-form-urlencoded{}{}v2{}",
+        headers["x-auth-nonce"].to_str().unwrap(),
+        headers["x-auth-timestamp"].to_str().unwrap(),
+        body
+    );
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"secret").unwrap();
+    mac.update(message.as_bytes());
+    assert_eq!(headers["x-auth-signature"], hex::encode(mac.finalize().into_bytes()).as_str());
+}
+
+#[tokio::test]
+async fn expired_good_till_date_is_rejected() {
+    let transport = FixtureTransport::new().route("buy/btcusd/", StatusCode::OK, "buy_order.json");
+    let bts = transport.client();
+    let request = bitstamp::types::LimitOrderRequest {
+        amount: "0.01".to_string(),
+        price: "43000.00".to_string(),
+        time_in_force: bitstamp::types::TimeInForce::GoodTillDate(1633024800),
+        ..Default::default()
+    };
+    assert!(bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", request).await.is_err());
+    assert!(transport.requests().is_empty());
+}
+
+#[tokio::test]
+async fn post_only_rejection_is_typed() {
+    let body = r#"{"status": "error", "reason": {"__all__": ["Maker or cancel order would be filled immediately."]}, "code": "API0012"}"#;
+    let transport = FixtureTransport::new().route_body("buy/btcusd/", StatusCode::BAD_REQUEST, body.to_string());
+    let bts = transport.client();
+    let request = |post_only| bitstamp::types::LimitOrderRequest {
+        amount: "0.01".to_string(),
+        price: "43300.00".to_string(),
+        post_only,
+        ..Default::default()
+    };
+
+    let e = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", request(true)).await.unwrap_err();
+    assert!(e.is_post_only_rejected(), "{}", e);
+    let e = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", request(false)).await.unwrap_err();
+    assert!(!e.is_post_only_rejected(), "{}", e);
+
+    let ioc = bitstamp::types::LimitOrderRequest { time_in_force: bitstamp::types::TimeInForce::ImmediateOrCancel, ..request(true) };
+    assert!(bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", ioc).await.is_err());
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn post_only_rejection_goes_by_error_code() {
+    // Reads like a maker-or-cancel rejection, but the code says otherwise
+    let body = r#"{"status": "error", "reason": "Order would be filled immediately, not enough balance.", "code": "API0016"}"#;
+    let bts = FixtureTransport::new().route_body("buy/btcusd/", StatusCode::BAD_REQUEST, body.to_string()).client();
+    let request = bitstamp::types::LimitOrderRequest { amount: "0.01".to_string(), price: "43300.00".to_string(), post_only: true, ..Default::default() };
+    let e = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", request).await.unwrap_err();
+    assert!(!e.is_post_only_rejected(), "{}", e);
+    assert_eq!(bitstamp::BitstampErrorCode::from_code("API0012"), bitstamp::BitstampErrorCode::MakerOrCancelRejected);
+}
+
+const DUPLICATE_CLIENT_ORDER_ID: &str = r#"{"status": "error", "reason": "Order with this client_order_id already exists.", "code": "API0020"}"#;
+
+fn retried_order(client_order_id: &str) -> bitstamp::types::LimitOrderRequest {
+    bitstamp::types::LimitOrderRequest {
+        amount: "0.02".to_string(),
+        price: "43300.00".to_string(),
+        client_order_id: Some(client_order_id.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn retry_hitting_duplicate_client_order_id_returns_the_open_order() {
+    let transport = FixtureTransport::new()
+        // Placed, but the reply was lost
+        .route_once("buy/btcusd/", StatusCode::SERVICE_UNAVAILABLE, common::fixture("maintenance.html"))
+        .route_body("buy/btcusd/", StatusCode::BAD_REQUEST, DUPLICATE_CLIENT_ORDER_ID.to_string())
+        .route("open_orders/all/", StatusCode::OK, "open_orders.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .max_retries(1)
+        .retry_backoff(std::time::Duration::from_millis(1))
+        .build();
+
+    let order = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", retried_order("grid-1")).await.unwrap();
+    // As Bitstamp has it, not as requested
+    assert_eq!(order.id, "1407531124125697");
+    assert_eq!(order.price, number("42000.00"));
+    assert_eq!(order.amount, number("0.01000000"));
+    assert_eq!(order.client_order_id.as_deref(), Some("grid-1"));
+    assert_eq!(transport.requests(), ["/api/v2/buy/btcusd/", "/api/v2/buy/btcusd/", "/api/v2/open_orders/all/"]);
+
+    // Without an earlier attempt the id really is taken
+    let e = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", retried_order("grid-1")).await.unwrap_err();
+    assert_eq!(e.code(), Some(bitstamp::BitstampErrorCode::DuplicateClientOrderId));
+}
+
+#[tokio::test]
+async fn retry_hitting_duplicate_client_order_id_returns_the_filled_order() {
+    let status = r#"{"id": 1407531124125699, "datetime": "2021-09-30 18:00:02", "type": "0", "status": "Finished",
+        "market": "BTC/USD", "amount_remaining": "0.00000000", "client_order_id": "grid-2", "transactions": [
+        {"tid": 1, "price": "43290.00", "fee": "0.43", "datetime": "2021-09-30 18:00:02", "type": 2, "btc": "0.01200000", "usd": "519.48"},
+        {"tid": 2, "price": "43300.00", "fee": "0.29", "datetime": "2021-09-30 18:00:02", "type": 2, "btc": 0.008, "usd": "346.40"}]}"#;
+    let transport = FixtureTransport::new()
+        .route_once("buy/btcusd/", StatusCode::SERVICE_UNAVAILABLE, common::fixture("maintenance.html"))
+        .route_body("buy/btcusd/", StatusCode::BAD_REQUEST, DUPLICATE_CLIENT_ORDER_ID.to_string())
+        .route("open_orders/all/", StatusCode::OK, "open_orders.json")
+        .route_body("order_status/", StatusCode::OK, status.to_string());
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .max_retries(1)
+        .retry_backoff(std::time::Duration::from_millis(1))
+        .build();
+
+    let order = bts.limit_order(bitstamp::types::TradeSide::Buy, "btcusd", retried_order("grid-2")).await.unwrap();
+    assert_eq!(order.id, "1407531124125699");
+    assert_eq!(order.datetime, "2021-09-30 18:00:02");
+    assert_eq!(order.amount.parse::<bitstamp::types::Decimal>().unwrap(), "0.02".parse().unwrap());
+    assert_eq!(order.price, number("43300.00"));
+    assert_eq!(order.client_order_id.as_deref(), Some("grid-2"));
+    assert!(transport.request_bodies()[3].contains("client_order_id=grid-2"));
+}
+
+#[tokio::test]
+async fn websocket_token_is_cached() {
+    let transport = FixtureTransport::new().route("websockets_token/", StatusCode::OK, "websockets_token.json");
+    let bts = transport.client();
+
+    assert_eq!(bts.websocket_token_cached().await.unwrap(), "7JbWzmno2sBMGCb3MeQUif5hIKgF1Rmq");
+    assert_eq!(bts.clone().websocket_token_cached().await.unwrap(), "7JbWzmno2sBMGCb3MeQUif5hIKgF1Rmq");
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_token_requests_share_one_fetch() {
+    let transport = FixtureTransport::new().route("websockets_token/", StatusCode::OK, "websockets_token.json");
+    let bts = transport.client();
+
+    let tasks: Vec<_> = (0..64)
+        .map(|_| {
+            let bts = bts.clone();
+            tokio::spawn(async move { bts.websocket_token_cached().await })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(task.await.unwrap().unwrap(), "7JbWzmno2sBMGCb3MeQUif5hIKgF1Rmq");
+    }
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn tickers_in_both_shapes() {
+    let bts = FixtureTransport::new()
+        .route("ticker/", StatusCode::OK, "tickers.json")
+        .route("ticker/btcusd/", StatusCode::OK, "ticker.json")
+        .client();
+
+    let single = bts.get_tickers_raw("ticker/btcusd/").await.unwrap();
+    assert_eq!(single.len(), 1);
+    assert_eq!(single[0].pair, "");
+    assert_eq!(single[0].ticker.last, "43210.55");
+
+    let all = bts.get_tickers_raw("ticker/").await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[1].url_symbol(), "etheur");
+    assert_eq!(all[0].ticker, single[0].ticker);
+    assert_eq!(all[0].ticker.last_side(), Some(bitstamp::types::TradeSide::Sell));
+    assert_eq!(all[0].ticker.percent_change_24().unwrap().to_string(), "-0.67");
+    assert_eq!((all[1].ticker.side.as_deref(), all[1].ticker.open_24()), (None, None));
+    assert_eq!(bts.get_all_tickers().await.unwrap(), all);
+}
+
+#[tokio::test]
+async fn compressed_reply_is_reported() {
+    let transport = FixtureTransport::new().route_with_headers(
+        "ticker/btcusd/",
+        StatusCode::OK,
+        "\u{1f}\u{8b}\u{8}\u{0}".to_string(),
+        &[("Content-Encoding", "gzip")],
+    );
+    let bts = transport.client();
+
+    let err = bts.get_ticker("btcusd").await.unwrap_err();
+    assert!(err.to_string().contains("gzip"));
+    assert_eq!(transport.request_headers()[0]["accept-encoding"], "identity");
+}
+
+#[tokio::test]
+async fn account_snapshot() {
+    let transport = FixtureTransport::new()
+        .route("balance/", StatusCode::OK, "balance.json")
+        .route("open_orders/all/", StatusCode::OK, "open_orders.json");
+    let before = chrono::Utc::now();
+    let snapshot = transport.client().account_snapshot().await.unwrap();
+
+    assert_eq!(snapshot.balance.btc_available, number("0.52000000"));
+    assert_eq!(snapshot.open_orders.len(), 2);
+    assert_eq!(snapshot.open_orders[0].client_order_id.as_deref(), Some("grid-1"));
+    assert_eq!(snapshot.open_orders[1].id, "1407531124125698");
+    assert_eq!(snapshot.open_orders[1].currency_pair, "ETH/EUR");
+    assert!(snapshot.captured_at >= before);
+    assert_eq!(transport.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn crypto_transactions() {
+    let bts = FixtureTransport::new()
+        .route("crypto-transactions/", StatusCode::OK, "crypto_transactions.json")
+        .client();
+
+    let transactions = bts.crypto_transactions(false, 100, 0).await.unwrap();
+    assert_eq!(transactions.deposits.len(), 1);
+    assert_eq!(transactions.deposits[0].amount, "0.5");
+    assert_eq!(transactions.deposits[0].datetime, "1633024700");
+    assert_eq!(transactions.withdrawals[0].destination_address, "0x8d12A197cB00D4747a1fe03395095ce2A5CC6819");
+    assert!(transactions.ripple_iou_transactions.is_empty());
+
+    let mut request = bitstamp::types::CryptoTransactionsRequest { limit: 100, offset: 0, include_ious: false };
+    assert!(!serde_json::to_string(&request).unwrap().contains("include_ious"));
+    request.include_ious = true;
+    assert!(serde_json::to_string(&request).unwrap().contains(r#""include_ious":true"#));
+}
+
+#[tokio::test]
+async fn balance_filtered_by_currency() {
+    let bts = FixtureTransport::new().route("balance/", StatusCode::OK, "balance.json").client();
+
+    let balances = bts.get_balance_filtered(&["BTC", "usd", "doge"]).await.unwrap();
+    assert_eq!(balances.len(), 2);
+    assert_eq!(balances["btc"].available, number("0.52000000"));
+    assert_eq!(balances["btc"].reserved, number("0.10000000"));
+    assert_eq!(balances["btc"].withdrawal_fee, Some(number("0.00050000")));
+    assert_eq!(balances["usd"].balance, "1520.35");
+
+    let all = bts.get_balance_filtered(&[]).await.unwrap();
+    assert_eq!(all.len(), 7);
+}
+
+#[tokio::test]
+async fn crypto_transactions_stream() {
+    use bitstamp::types::CryptoTransactionKind;
+    use futures::TryStreamExt;
+
+    let transport = FixtureTransport::new().route("crypto-transactions/", StatusCode::OK, "crypto_transactions.json");
+    let bts = transport.client();
+
+    let all: Vec<_> = bts.crypto_transactions_stream(false).try_collect().await.unwrap();
+    let kinds: Vec<CryptoTransactionKind> = all.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [CryptoTransactionKind::Withdrawal, CryptoTransactionKind::Deposit]);
+    assert_eq!(all[1].1.currency, "BTC");
+    // A short page is the last one
+    assert_eq!(transport.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn crypto_transactions_stream_pages_until_a_short_page() {
+    use bitstamp::types::CryptoTransactionKind;
+    use futures::TryStreamExt;
+
+    // Full pages of deposits, newest first, then a short one holding the oldest withdrawal
+    let page = |kind: &str, first: i64, len: i64| {
+        let transactions: Vec<serde_json::Value> = (first..first + len)
+            .map(|i| serde_json::json!({"currency": "BTC", "destinationAddress": "addr", "txid": format!("tx{}", i), "amount": "0.1", "datetime": 1633024700 - i}))
+            .collect();
+        serde_json::json!({ kind: transactions }).to_string()
+    };
+    let transport = FixtureTransport::new()
+        .route_once("crypto-transactions/", StatusCode::OK, page("deposits", 0, 1000))
+        .route_once("crypto-transactions/", StatusCode::OK, page("deposits", 1000, 1000))
+        .route_once("crypto-transactions/", StatusCode::OK, page("withdrawals", 2000, 1));
+    let bts = transport.client();
+
+    let all: Vec<_> = bts.crypto_transactions_stream(false).try_collect().await.unwrap();
+    assert_eq!(all.len(), 2001);
+    let txids: Vec<String> = all.iter().map(|(_, t)| t.txid.clone()).collect();
+    // Still newest first across the page boundaries
+    let expected: Vec<String> = (0..2001).map(|i| format!("tx{}", i)).collect();
+    assert_eq!(txids, expected);
+    assert_eq!(all[2000].0, CryptoTransactionKind::Withdrawal);
+
+    assert_eq!(transport.requests().len(), 3);
+    let bodies = transport.request_bodies();
+    assert!(bodies[0].contains("offset=0"));
+    assert!(bodies[1].contains("offset=1000"));
+    assert!(bodies[2].contains("offset=2000"));
+}
+
+#[tokio::test]
+async fn auth_timestamp_is_epoch_millis() {
+    let transport = FixtureTransport::new().route("balance/", StatusCode::OK, "balance.json");
+    let bts = transport.client();
+    let before = chrono::Utc::now().timestamp_millis();
+    bts.get_balance().await.unwrap();
+    let after = chrono::Utc::now().timestamp_millis();
+
+    let timestamp = transport.request_headers()[0]["x-auth-timestamp"].to_str().unwrap().to_string();
+    assert_eq!(timestamp.len(), 13);
+    let timestamp: i64 = timestamp.parse().unwrap();
+    assert!(before <= timestamp && timestamp <= after);
+}
+
+#[tokio::test]
+async fn place_orders_keeps_input_order() {
+    use bitstamp::types::{LimitOrderRequest, MarketOrderRequest, OrderRequest, TradeSide};
+
+    let transport = FixtureTransport::new()
+        .route("buy/btcusd/", StatusCode::OK, "buy_order.json")
+        .route("sell/market/btcusd/", StatusCode::OK, "buy_order.json");
+    let bts = transport.client();
+    let limit = |pair: &str| OrderRequest::Limit {
+        side: TradeSide::Buy,
+        currency_pair: pair.to_string(),
+        request: LimitOrderRequest { amount: "0.01".to_string(), price: "43000.00".to_string(), ..Default::default() },
+    };
+    let orders = vec![
+        limit("btcusd"),
+        limit("dogeusd"),
+        OrderRequest::Market {
+            side: TradeSide::Sell,
+            currency_pair: "btcusd".to_string(),
+            request: MarketOrderRequest { amount: "0.01".to_string(), client_order_id: None },
+        },
+    ];
+
+    let results = bts.place_orders(orders).await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().id, "1407531124125696");
+    assert_eq!(results[1].as_ref().unwrap_err().status(), Some(StatusCode::NOT_FOUND));
+    assert!(results[2].is_ok());
+    assert_eq!(transport.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn cancel_limit_and_market_orders() {
+    let bts = FixtureTransport::new().route("cancel_order/", StatusCode::OK, "cancel_limit_order.json").client();
+    let limit = bts.cancel_order("1405925551976448").await.unwrap();
+    assert_eq!(limit.id, "1405925551976448");
+    assert_eq!(limit.price, Some(number("43000.0")));
+    assert_eq!(limit.type_field.as_deref(), Some("0"));
+
+    let bts = FixtureTransport::new().route("cancel_order/", StatusCode::OK, "cancel_market_order.json").client();
+    let market = bts.cancel_order("1405925570437121").await.unwrap();
+    assert_eq!(market.amount.as_deref(), Some("0.5"));
+    assert_eq!(market.price, None);
+    assert_eq!(market.type_field.as_deref(), Some("1"));
+}
+
+#[tokio::test]
+async fn endpoints_of_either_api_version() {
+    let transport = FixtureTransport::new().route("eur_usd/", StatusCode::OK, "eur_usd.json");
+    let bts = transport.client();
+    let v2: serde_json::Value = bts.raw_get("eur_usd/").await.unwrap();
+    let v1: serde_json::Value = bts.raw_get_version(bitstamp::ApiVersion::V1, "eur_usd/").await.unwrap();
+    assert_eq!(v1, v2);
+    assert_eq!(transport.requests(), ["/api/v2/eur_usd/", "/api/eur_usd/"]);
+}
+
+#[tokio::test]
+async fn rate_limit_paces_all_clones() {
+    let transport = FixtureTransport::new().route("eur_usd/", StatusCode::OK, "eur_usd.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .rate_limit(50)
+        .build();
+
+    // A burst of 50, then 20ms apart
+    let start = std::time::Instant::now();
+    let requests = (0..60).map(|_| {
+        let bts = bts.clone();
+        async move { bts.raw_get::<serde_json::Value>("eur_usd/").await }
+    });
+    for reply in futures::future::join_all(requests).await {
+        reply.unwrap();
+    }
+    assert!(start.elapsed() >= std::time::Duration::from_millis(190), "{:?}", start.elapsed());
+    assert_eq!(transport.requests().len(), 60);
+}
+
+#[tokio::test]
+async fn user_transaction_by_id() {
+    let transport = FixtureTransport::new().route("user_transactions/", StatusCode::OK, "user_transactions.json");
+    let bts = transport.client();
+
+    let transaction = bts.user_transaction(207411233).await.unwrap();
+    assert_eq!(transaction.type_field, "1");
+    assert!(bts.user_transaction(207411234).await.is_err());
+    assert_eq!(transport.requests(), ["/api/v2/user_transactions/", "/api/v2/user_transactions/"]);
+}
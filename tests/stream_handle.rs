@@ -0,0 +1,47 @@
+use bitstamp::types::{CurrencyPairs, EventChannel, EventEvent};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+    "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+    "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+
+#[tokio::test]
+async fn subscribe_through_handle_while_reading() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let subscribe = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains("bts:subscribe") && subscribe.contains("live_trades_btcusd"));
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        let unsubscribe = ws.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(unsubscribe.contains("bts:unsubscribe"));
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let handle = stream.handle();
+    let (events, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+    let reader = tokio::spawn(async move {
+        while let Ok(event) = stream.next().await {
+            if events.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let channel = EventChannel::LiveTrades(CurrencyPairs::Btcusd);
+    handle.subscribe(channel.clone()).await.unwrap();
+    assert_eq!(events_rx.recv().await.unwrap().event, EventEvent::Trade);
+    handle.clone().unsubscribe(channel).await.unwrap();
+
+    // Dropping the stream closes the handle
+    reader.abort();
+    let _ = reader.await;
+    assert!(handle.subscribe(EventChannel::LiveTrades(CurrencyPairs::Etheur)).await.is_err());
+}
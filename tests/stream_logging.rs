@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitstamp::types::{CurrencyPairs, EventChannel, EventEvent};
+use bitstamp::Bitstamp;
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Capture;
+
+impl log::Log for Capture {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("bitstamp")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            LINES.lock().unwrap().push(format!("{} {}", record.level(), record.args()));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[tokio::test]
+async fn reconnect_timeline_is_logged() {
+    log::set_logger(&Capture).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.close(Some(CloseFrame { code: CloseCode::Away, reason: "maintenance".into() })).await.unwrap();
+
+        for _ in 0..2 {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        }
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_auto_reconnect(true)
+        .ws_reconnect_delay(Duration::from_millis(10))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    stream.subscribe(EventChannel::LiveTrades(CurrencyPairs::Btcusd)).await.unwrap();
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Reconnected);
+
+    let lines = LINES.lock().unwrap().clone();
+    let expected = [
+        "INFO WebSocket connected to",
+        "WARN WebSocket disconnected from",
+        "INFO WebSocket reconnect attempt 1",
+        "INFO WebSocket connected to",
+        "DEBUG Resubscribing LiveTrades(Btcusd)",
+        "INFO WebSocket reconnected after 1 attempt(s), 1 channel(s) resubscribed",
+    ];
+    let mut lines_left = lines.iter();
+    for prefix in expected.iter() {
+        assert!(lines_left.any(|line| line.starts_with(prefix)), "{:?} not in order in {:#?}", prefix, lines);
+    }
+    assert!(lines.iter().any(|line| line.contains("code 1001")), "{:#?}", lines);
+
+    // Dropping an open stream is flagged, closing it isn't
+    let dropped = |lines: &[String]| lines.iter().filter(|line| line.contains("dropped without close()")).count();
+    drop(stream);
+    assert_eq!(dropped(&LINES.lock().unwrap()), 1);
+    bts.event_stream().await.unwrap().close().await.unwrap();
+    assert_eq!(dropped(&LINES.lock().unwrap()), 1);
+}
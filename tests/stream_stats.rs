@@ -0,0 +1,119 @@
+use bitstamp::types::{CurrencyPairs, EventChannel, EventEvent};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+    "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+    "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+
+#[tokio::test]
+async fn counts_frames() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.send(Message::Ping(vec![])).await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        ws.send(Message::Text("not json".to_string())).await.unwrap();
+        // Keep the connection open until the client is done
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let event = stream.next().await.unwrap();
+    assert_eq!(event.event, EventEvent::Trade);
+    assert_eq!(event.channel, EventChannel::LiveTrades(CurrencyPairs::Btcusd));
+    assert!(stream.next().await.is_err());
+
+    let stats = stream.stats();
+    assert_eq!(stats.messages_received, 3);
+    assert_eq!(stats.pings, 1);
+    assert_eq!(stats.parse_failures, 1);
+    assert_eq!(stats.reconnects, 0);
+}
+
+#[tokio::test]
+async fn tracks_activity_per_channel() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.next().await.unwrap().unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let busy = EventChannel::LiveTrades(CurrencyPairs::Btcusd);
+    let quiet = EventChannel::LiveTrades(CurrencyPairs::Etheur);
+    stream.subscribe(busy.clone()).await.unwrap();
+    stream.subscribe(quiet.clone()).await.unwrap();
+    let before = chrono::Utc::now();
+    stream.next().await.unwrap();
+
+    let activity = stream.channel_activity();
+    assert_eq!(activity.len(), 2);
+    assert!(activity[&busy].unwrap() >= before);
+    assert_eq!(activity[&quiet], None);
+}
+
+#[tokio::test]
+async fn raw_hook_sees_every_payload() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        ws.send(Message::Ping(vec![])).await.unwrap();
+        ws.send(Message::Binary(b"not json".to_vec())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let captured = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = captured.clone();
+    stream.set_raw_hook(Box::new(move |payload| sink.lock().unwrap().push(payload.to_string())));
+
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+    assert!(stream.next().await.is_err());
+    assert_eq!(*captured.lock().unwrap(), [TRADE, "not json"]);
+}
+
+#[tokio::test]
+async fn drops_duplicate_events() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let other = TRADE.replace(r#""id": 2"#, r#""id": 5"#);
+        let later = TRADE.replace(r#""id": 2"#, r#""id": 6"#).replace("1633024800123456", "1633024810123456");
+        for frame in [TRADE.to_string(), TRADE.to_string(), other.clone(), other, later, TRADE.to_string()] {
+            ws.send(Message::Text(frame)).await.unwrap();
+        }
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    stream.set_dedup(std::time::Duration::from_secs(5));
+    let mut ids = Vec::new();
+    for _ in 0..4 {
+        match stream.next().await.unwrap().data {
+            bitstamp::types::EventData::Trade { id, .. } => ids.push(id),
+            data => panic!("unexpected {:?}", data),
+        }
+    }
+    // The last replay is older than the window, so it's no longer recognized
+    assert_eq!(ids, [2, 5, 6, 2]);
+    assert_eq!(stream.stats().duplicates, 2);
+}
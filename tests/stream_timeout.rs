@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use bitstamp::types::EventEvent;
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str = r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+    "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+    "price_str": "43210.55", "type": 0, "price": 43210.55}}"#;
+
+#[tokio::test]
+async fn quiet_period_is_not_an_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (go, wait) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        wait.await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_timeout(Duration::from_millis(50))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    assert_eq!(stream.try_next().await.unwrap(), None);
+    assert_eq!(stream.try_next().await.unwrap(), None);
+
+    go.send(()).unwrap();
+    let event = stream.try_next().await.unwrap().unwrap();
+    assert_eq!(event.event, EventEvent::Trade);
+    assert!(stream.next().await.is_err());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use bitstamp::types::{OrderBook, Ticker, Transaction};
+
+const STRICT: bool = cfg!(feature = "strict-decimals");
+
+#[test]
+fn fixtures_pass_strict_checks() {
+    serde_json::from_str::<Ticker>(&common::fixture("ticker.json")).unwrap();
+    serde_json::from_str::<Vec<Transaction>>(&common::fixture("transactions.json")).unwrap();
+    serde_json::from_str::<OrderBook>(&common::fixture("order_book.json")).unwrap();
+}
+
+#[test]
+fn non_numeric_ticker_field_is_named() {
+    let json = common::fixture("ticker.json").replace(r#""vwap": "43650.80""#, r#""vwap": "n/a""#);
+    let ticker = serde_json::from_str::<Ticker>(&json);
+    if STRICT {
+        let err = ticker.unwrap_err().to_string();
+        assert!(err.contains("vwap is not a decimal: \"n/a\""), "{}", err);
+    } else {
+        assert_eq!(ticker.unwrap().vwap, "n/a");
+    }
+}
+
+#[test]
+fn non_numeric_transaction_field_is_named() {
+    let json = r#"{"date": "1633024799", "tid": "198765432", "price": "", "type": "0", "amount": "0.01250000"}"#;
+    let transaction = serde_json::from_str::<Transaction>(json);
+    if STRICT {
+        assert!(transaction.unwrap_err().to_string().contains("price is not a decimal"));
+    } else {
+        assert_eq!(transaction.unwrap().price, "");
+    }
+}
+
+#[test]
+fn malformed_order_book_level_is_named() {
+    let bad_amount = r#"{"timestamp": "1633024800", "microtimestamp": "1633024800123456", "bids": [], "asks": [["43212.40", "NaN?"]]}"#;
+    let short_level = r#"{"timestamp": "1633024800", "microtimestamp": "1633024800123456", "bids": [["43205.12"]], "asks": []}"#;
+    if STRICT {
+        assert!(serde_json::from_str::<OrderBook>(bad_amount).unwrap_err().to_string().contains("asks is not a decimal"));
+        assert!(serde_json::from_str::<OrderBook>(short_level).unwrap_err().to_string().contains("bids has a level without price and amount"));
+    } else {
+        assert!(serde_json::from_str::<OrderBook>(bad_amount).is_ok());
+        assert!(serde_json::from_str::<OrderBook>(short_level).is_ok());
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use bitstamp::types::{CurrencyPairs, EventChannel, EventEvent};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+#[tokio::test]
+async fn reports_failures_per_channel() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        for _ in 0..4 {
+            ws.next().await.unwrap().unwrap();
+        }
+        let replies = [
+            r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#,
+            r#"{"event": "trade", "channel": "live_trades_btcusd", "data": {"buy_order_id": 1, "amount_str": "0.1",
+                "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": 2, "amount": 0.1, "sell_order_id": 3,
+                "price_str": "43210.55", "type": 0, "price": 43210.55}}"#,
+            r#"{"event": "bts:subscription_failed", "channel": "live_trades_etheur", "data": {"message": "Unknown channel"}}"#,
+            r#"{"event": "bts:error", "channel": "", "data": {"code": null, "message": "Bad subscription string."}}"#,
+        ];
+        for reply in replies.iter() {
+            ws.send(Message::Text(reply.to_string())).await.unwrap();
+        }
+        while ws.next().await.is_some() {}
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_timeout(Duration::from_millis(300))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let channels = [
+        EventChannel::LiveTrades(CurrencyPairs::Btcusd),
+        EventChannel::LiveTrades(CurrencyPairs::Etheur),
+        EventChannel::LiveTrades(CurrencyPairs::Ltcusd),
+        EventChannel::LiveTrades(CurrencyPairs::Xrpusd),
+    ];
+    let report = stream.subscribe_all(&channels).await.unwrap();
+    assert!(!report.is_complete());
+    assert_eq!(report.subscribed, [channels[0].clone()]);
+    assert_eq!(stream.subscriptions(), [channels[0].clone()]);
+
+    let failed: Vec<&EventChannel> = report.failed.iter().map(|(channel, _)| channel).collect();
+    assert_eq!(failed, [&channels[1], &channels[2], &channels[3]]);
+    assert!(report.failed[0].1.to_string().contains("Unknown channel"));
+    assert!(report.failed[1].1.to_string().contains("Bad subscription string."));
+    assert!(report.failed[2].1.is_timeout());
+
+    // The trade that arrived in between is still delivered
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+}
+
+#[tokio::test]
+async fn streaming_data_does_not_hold_off_the_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        for _ in 0..2 {
+            ws.next().await.unwrap().unwrap();
+        }
+        let confirmed = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#;
+        ws.send(Message::Text(confirmed.to_string())).await.unwrap();
+        // Trades keep coming faster than the timeout, the second channel never gets a reply
+        for id in 0.. {
+            let trade = format!(
+                r#"{{"event": "trade", "channel": "live_trades_btcusd", "data": {{"buy_order_id": 1, "amount_str": "0.1",
+                "timestamp": "1633024800", "microtimestamp": "1633024800123456", "id": {}, "amount": 0.1, "sell_order_id": 3,
+                "price_str": "43210.55", "type": 0, "price": 43210.55}}}}"#,
+                id
+            );
+            if ws.send(Message::Text(trade)).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_timeout(Duration::from_millis(300))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let channels = [EventChannel::LiveTrades(CurrencyPairs::Btcusd), EventChannel::LiveTrades(CurrencyPairs::Etheur)];
+    let report = tokio::time::timeout(Duration::from_secs(2), stream.subscribe_all(&channels)).await.expect("subscribe_all kept waiting").unwrap();
+    assert_eq!(report.subscribed, [channels[0].clone()]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, channels[1]);
+    assert!(report.failed[0].1.is_timeout());
+
+    assert_eq!(stream.next().await.unwrap().event, EventEvent::Trade);
+}
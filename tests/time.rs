@@ -0,0 +1,14 @@
+use std::str::FromStr;
+
+use bitstamp::types::Time;
+
+#[test]
+fn wire_forms_agree() {
+    for t in [Time::Minute, Time::Hour, Time::Day].iter() {
+        assert_eq!(Time::from_str(&t.to_string().to_lowercase()), Ok(*t));
+        assert_eq!(serde_json::to_string(t).unwrap(), format!("\"{}\"", t.as_str()));
+        assert_eq!(serde_json::from_str::<Time>(&format!("\"{}\"", t)).unwrap(), *t);
+    }
+    assert_eq!(Time::from_str("Hour"), Ok(Time::Hour));
+    assert!(Time::from_str("week").is_err());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use bitstamp::types::CurrencyPairs;
+use common::FixtureTransport;
+use futures_util::{SinkExt, StreamExt};
+use hyper::StatusCode;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+fn trade(id: i64) -> String {
+    format!(
+        r#"{{"event": "trade", "channel": "live_trades_btcusd", "data": {{"buy_order_id": 1, "amount_str": "0.1",
+        "timestamp": "1633024801", "microtimestamp": "1633024801123456", "id": {}, "amount": 0.1, "sell_order_id": 3,
+        "price_str": "43211.00", "type": 1, "price": 43211.0}}}}"#,
+        id
+    )
+}
+
+#[tokio::test]
+async fn backfill_hands_off_to_live_trades() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        // Wait for the subscription, then replay trades the backfill already has
+        ws.next().await.unwrap().unwrap();
+        for id in [198765431, 198765432, 198765433] {
+            ws.send(Message::Text(trade(id))).await.unwrap();
+        }
+        while ws.next().await.is_some() {}
+    });
+
+    let transport = FixtureTransport::new().route("transactions/btcusd/?time=hour", StatusCode::OK, "transactions.json");
+    let bts = bitstamp::Bitstamp::builder("secret".to_string(), "key".to_string())
+        .transport(transport.clone())
+        .ws_url(&url)
+        .build();
+
+    let mut trades = bts.trades_with_backfill(CurrencyPairs::Btcusd, 2).await.unwrap();
+    let mut tids = Vec::new();
+    for _ in 0..3 {
+        tids.push(trades.next().await.unwrap().tid);
+    }
+    assert_eq!(tids, ["198765431", "198765432", "198765433"]);
+    assert_eq!(transport.requests(), ["/api/v2/transactions/btcusd/?time=hour"]);
+}
@@ -0,0 +1,24 @@
+use bitstamp::types::UserTransaction;
+
+fn parse(json: &str) -> UserTransaction {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn trade_fee_is_in_counter_currency() {
+    let trade = parse(r#"{"id": 1, "datetime": "2021-09-30 18:00:00", "type": "2", "fee": "0.21600",
+        "btc": "0.01000000", "usd": "-432.10", "btc_usd": 43210.0, "eur": 0.0, "order_id": 1407531124125696}"#);
+    assert_eq!(trade.fee_currency(), Some("usd"));
+    let fee = if cfg!(feature = "normalize-decimals") { "0.216" } else { "0.21600" };
+    assert_eq!(trade.fee_decimal().unwrap().to_string(), fee);
+}
+
+#[test]
+fn transfer_fee_is_in_moved_currency() {
+    let withdrawal = parse(r#"{"id": 2, "datetime": "2021-09-30 18:00:00", "type": "1", "fee": "0.0005",
+        "btc": "-0.5", "usd": "0.0", "eur": 0}"#);
+    assert_eq!(withdrawal.fee_currency(), Some("btc"));
+
+    let unknown = parse(r#"{"id": 3, "datetime": "2021-09-30 18:00:00", "type": "14", "fee": "0", "usd": "0.0"}"#);
+    assert_eq!(unknown.fee_currency(), None);
+}
@@ -0,0 +1,47 @@
+use bitstamp::types::{CurrencyPairs, EventChannel};
+use bitstamp::Bitstamp;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Serve a single ~2 KiB order book snapshot, returns the server url
+async fn serve_snapshot() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let levels: Vec<String> = (0..64).map(|i| format!(r#"["{}.00", "0.50000000"]"#, 43000 + i)).collect();
+        let snapshot = format!(
+            r#"{{"event": "data", "channel": "order_book_btcusd", "data": {{"timestamp": "1633024800", "microtimestamp": "1633024800123456", "bids": [{}], "asks": [{}]}}}}"#,
+            levels.join(", "),
+            levels.join(", ")
+        );
+        assert!(snapshot.len() > 2048);
+        ws.send(Message::Text(snapshot)).await.unwrap();
+        while ws.next().await.is_some() {}
+    });
+    url
+}
+
+#[tokio::test]
+async fn default_limits_accept_snapshots() {
+    let url = serve_snapshot().await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string()).ws_url(&url).build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let event = stream.next().await.unwrap();
+    assert_eq!(event.channel, EventChannel::OrderBook(CurrencyPairs::Btcusd));
+}
+
+#[tokio::test]
+async fn oversized_message_is_an_error() {
+    let url = serve_snapshot().await;
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_max_message_size(1024)
+        .ws_max_frame_size(1024)
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = stream.next().await.unwrap_err();
+    assert!(err.to_string().contains("ws_max_message_size"), "{}", err);
+}
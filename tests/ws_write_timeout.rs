@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use bitstamp::types::{CurrencyPairs, EventChannel};
+use bitstamp::Bitstamp;
+use tokio::net::TcpSocket;
+
+#[tokio::test]
+async fn stalled_sink_times_out() {
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(1).unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        // Complete the handshake, then never read again
+        let _ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let _ = stopped.await;
+    });
+
+    let bts = Bitstamp::builder("secret".to_string(), "key".to_string())
+        .ws_url(&url)
+        .ws_write_timeout(Duration::from_millis(200))
+        .build();
+    let mut stream = bts.event_stream().await.unwrap();
+    let err = loop {
+        // Fills the socket buffers until a send can't complete
+        if let Err(e) = stream.subscribe(EventChannel::LiveTrades(CurrencyPairs::Btcusd)).await {
+            break e;
+        }
+    };
+    assert!(err.is_timeout(), "{}", err);
+    let _ = stop.send(());
+}